@@ -0,0 +1,93 @@
+//! Times matrix multiplication on the shapes `Network::evaluate` uses for a
+//! snake, plus a couple of larger ones, against the column-walking multiply
+//! it replaced. Run with `cargo bench --bench matmul`.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use neural::{rng, Matrix};
+use rand::Rng;
+
+// Enough to run each case for a good fraction of a second.
+const TARGET_TIME: Duration = Duration::from_millis(500);
+
+fn main() {
+    let mut rng = rng::from_seed(0);
+    let mut random_matrix = |height: usize, width: usize| {
+        Matrix::new_map(height, width, |_row: usize, _col: usize| {
+            rng.gen_range(-1.0..1.0)
+        })
+    };
+
+    println!(
+        "{:<16} {:>14} {:>14} {:>14} {:>9}",
+        "shape", "old ns/iter", "mul ns/iter", "into ns/iter", "speedup"
+    );
+
+    // A snake's network, one layer at a time: 6 inputs, three hidden layers
+    // of 8, and 4 outputs.
+    let cases = [
+        (1, 6, 8),
+        (1, 8, 8),
+        (1, 8, 4),
+        (16, 64, 64),
+        (64, 64, 64),
+        (256, 256, 256),
+    ];
+    for &(height, inner, width) in cases.iter() {
+        let lhs = random_matrix(height, inner);
+        let rhs = random_matrix(inner, width);
+        let mut out = Matrix::new(height, width);
+
+        let old = time(|| old_mul(black_box(&lhs), black_box(&rhs)));
+        let new = time(|| black_box(&lhs) * black_box(&rhs));
+        let into = time(|| black_box(&lhs).mul_into(black_box(&rhs), &mut out));
+
+        println!(
+            "{:<16} {:>14.1} {:>14.1} {:>14.1} {:>8.1}x",
+            format!("{}x{} * {}x{}", height, inner, inner, width),
+            old,
+            new,
+            into,
+            old / into
+        );
+    }
+}
+
+// Mean nanoseconds per call of `func`, after a warm up.
+fn time<F, R>(mut func: F) -> f64
+where
+    F: FnMut() -> R,
+{
+    let mut iterations: u64 = 1;
+    loop {
+        let start = Instant::now();
+        for _ in 0..iterations {
+            black_box(func());
+        }
+
+        let elapsed = start.elapsed();
+        if elapsed >= TARGET_TIME {
+            return elapsed.as_nanos() as f64 / iterations as f64;
+        }
+
+        iterations *= 2;
+    }
+}
+
+// The multiply as it used to be: every value built through an intermediate
+// matrix of `Option`s, summing down a column of `rhs` at a time.
+fn old_mul(lhs: &Matrix<f64>, rhs: &Matrix<f64>) -> Matrix<f64> {
+    let mut options_matrix: Matrix<Option<f64>> = Matrix::new(lhs.get_height(), rhs.get_width());
+    for row in 0..lhs.get_height() {
+        for col in 0..rhs.get_width() {
+            let mut values_iter = (0..lhs.get_width()).map(|i| lhs[row][i] * rhs[i][col]);
+            let first_item = values_iter.next().unwrap();
+            options_matrix[row][col] = Some(values_iter.fold(first_item, |sum, val| sum + val));
+        }
+    }
+
+    Matrix::new_map(lhs.get_height(), rhs.get_width(), |row, col| {
+        options_matrix[row][col].unwrap()
+    })
+}
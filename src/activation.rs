@@ -0,0 +1,314 @@
+use crate::format::{self, FormatError};
+use crate::network::Network;
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+
+/// The function a network layer applies to its neurons' weighted sums.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Activation {
+    /// Squashes into (0, 1).
+    Sigmoid,
+    /// Squashes into (-1, 1).
+    Tanh,
+    /// Zero for negative values, and unchanged otherwise.
+    Relu,
+    /// Like `Relu`, but negative values are scaled by `slope` rather than
+    /// zeroed, so those neurons can still recover.
+    LeakyRelu { slope: f64 },
+    /// Like `Relu`, but negative values curve smoothly down towards `-alpha`.
+    Elu { alpha: f64 },
+    /// Squashes into (-1, 1) like `Tanh`, but approaches the limits more
+    /// slowly.
+    Softsign,
+    /// Leaves values unchanged.
+    Linear,
+    /// Turns the whole layer into probabilities that sum to 1. Only makes
+    /// sense for the output layer.
+    Softmax,
+}
+
+impl Activation {
+    /// Applies the activation in place to the values of one layer.
+    pub fn apply(&self, values: &mut [f64]) {
+        match *self {
+            Activation::Sigmoid => Activation::map(values, Network::sigmoid),
+            Activation::Tanh => Activation::map(values, f64::tanh),
+            Activation::Relu => Activation::map(values, |val| val.max(0.0)),
+            Activation::LeakyRelu { slope } => {
+                Activation::map(values, |val| if val > 0.0 { val } else { slope * val })
+            }
+            Activation::Elu { alpha } => {
+                Activation::map(
+                    values,
+                    |val| {
+                        if val > 0.0 {
+                            val
+                        } else {
+                            alpha * val.exp_m1()
+                        }
+                    },
+                )
+            }
+            Activation::Softsign => Activation::map(values, |val| val / (1.0 + val.abs())),
+            Activation::Linear => {}
+            Activation::Softmax => {
+                // Shifting by the largest value keeps `exp` from overflowing,
+                // and cancels out once normalised.
+                let largest = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+                let mut total = 0.0;
+                for val in values.iter_mut() {
+                    *val = (*val - largest).exp();
+                    total += *val;
+                }
+                Activation::map(values, |val| val / total);
+            }
+        }
+    }
+
+    /// Turns `gradient`, the gradient of a loss with respect to the values
+    /// of one layer, into the gradient with respect to the weighted sums
+    /// that went into them. `sums` are those weighted sums, and `values` the
+    /// result of `apply` on them.
+    pub fn backprop(&self, sums: &[f64], values: &[f64], gradient: &mut [f64]) {
+        let chain = |gradient: &mut [f64], derivative: &dyn Fn(f64, f64) -> f64| {
+            for ((grad, &sum), &val) in gradient.iter_mut().zip(sums.iter()).zip(values.iter()) {
+                *grad *= derivative(sum, val);
+            }
+        };
+
+        match *self {
+            Activation::Sigmoid => chain(gradient, &|_, val| val * (1.0 - val)),
+            Activation::Tanh => chain(gradient, &|_, val| 1.0 - val * val),
+            Activation::Relu => chain(gradient, &|sum, _| if sum > 0.0 { 1.0 } else { 0.0 }),
+            Activation::LeakyRelu { slope } => {
+                chain(gradient, &|sum, _| if sum > 0.0 { 1.0 } else { slope })
+            }
+            Activation::Elu { alpha } => chain(gradient, &|sum, _| {
+                if sum > 0.0 {
+                    1.0
+                } else {
+                    alpha * sum.exp()
+                }
+            }),
+            Activation::Softsign => chain(gradient, &|sum, _| (1.0 + sum.abs()).powi(-2)),
+            Activation::Linear => {}
+            Activation::Softmax => {
+                // Every output depends on every sum, so this is the full
+                // Jacobian product rather than one derivative per value.
+                let dot = gradient
+                    .iter()
+                    .zip(values.iter())
+                    .map(|(&grad, &val)| grad * val)
+                    .sum::<f64>();
+                for (grad, &val) in gradient.iter_mut().zip(values.iter()) {
+                    *grad = val * (*grad - dot);
+                }
+            }
+        }
+    }
+
+    /// The lowest and highest values the activation can give, or `None` if
+    /// it's unbounded either way.
+    pub fn range(&self) -> Option<(f64, f64)> {
+        match *self {
+            Activation::Sigmoid | Activation::Softmax => Some((0.0, 1.0)),
+            Activation::Tanh | Activation::Softsign => Some((-1.0, 1.0)),
+            Activation::Relu
+            | Activation::LeakyRelu { .. }
+            | Activation::Elu { .. }
+            | Activation::Linear => None,
+        }
+    }
+
+    fn map(values: &mut [f64], func: impl Fn(f64) -> f64) {
+        for val in values.iter_mut() {
+            *val = func(*val);
+        }
+    }
+
+    pub(crate) fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        match *self {
+            Activation::Sigmoid => format::write_u8(writer, 0),
+            Activation::Tanh => format::write_u8(writer, 1),
+            Activation::Relu => format::write_u8(writer, 2),
+            Activation::LeakyRelu { slope } => {
+                format::write_u8(writer, 3)?;
+                format::write_f64(writer, slope)
+            }
+            Activation::Elu { alpha } => {
+                format::write_u8(writer, 4)?;
+                format::write_f64(writer, alpha)
+            }
+            Activation::Softsign => format::write_u8(writer, 5),
+            Activation::Linear => format::write_u8(writer, 6),
+            Activation::Softmax => format::write_u8(writer, 7),
+        }
+    }
+
+    pub(crate) fn read_from(reader: &mut impl Read) -> Result<Self, FormatError> {
+        match format::read_u8(reader)? {
+            0 => Ok(Activation::Sigmoid),
+            1 => Ok(Activation::Tanh),
+            2 => Ok(Activation::Relu),
+            3 => Ok(Activation::LeakyRelu {
+                slope: format::read_f64(reader)?,
+            }),
+            4 => Ok(Activation::Elu {
+                alpha: format::read_f64(reader)?,
+            }),
+            5 => Ok(Activation::Softsign),
+            6 => Ok(Activation::Linear),
+            7 => Ok(Activation::Softmax),
+            other => Err(FormatError::Invalid(format!(
+                "{} is not a valid activation",
+                other
+            ))),
+        }
+    }
+}
+
+/// Written the same way it's parsed, e.g. `relu` or `leaky-relu:0.01`.
+impl fmt::Display for Activation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Activation::Sigmoid => write!(f, "sigmoid"),
+            Activation::Tanh => write!(f, "tanh"),
+            Activation::Relu => write!(f, "relu"),
+            Activation::LeakyRelu { slope } => write!(f, "leaky-relu:{}", slope),
+            Activation::Elu { alpha } => write!(f, "elu:{}", alpha),
+            Activation::Softsign => write!(f, "softsign"),
+            Activation::Linear => write!(f, "linear"),
+            Activation::Softmax => write!(f, "softmax"),
+        }
+    }
+}
+
+impl FromStr for Activation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, param) = match s.split_once(':') {
+            Some((name, param)) => (name, Some(param)),
+            None => (s, None),
+        };
+
+        let parse_param = |param: &str, what: &str| {
+            param
+                .parse::<f64>()
+                .ok()
+                .filter(|val| val.is_finite())
+                .ok_or_else(|| format!("Invalid {} '{}'", what, param))
+        };
+
+        match (name, param) {
+            ("sigmoid", None) => Ok(Activation::Sigmoid),
+            ("tanh", None) => Ok(Activation::Tanh),
+            ("relu", None) => Ok(Activation::Relu),
+            ("leaky-relu", None) => Ok(Activation::LeakyRelu { slope: 0.01 }),
+            ("leaky-relu", Some(slope)) => Ok(Activation::LeakyRelu {
+                slope: parse_param(slope, "leaky ReLU slope")?,
+            }),
+            ("elu", None) => Ok(Activation::Elu { alpha: 1.0 }),
+            ("elu", Some(alpha)) => Ok(Activation::Elu {
+                alpha: parse_param(alpha, "ELU alpha")?,
+            }),
+            ("softsign", None) => Ok(Activation::Softsign),
+            ("linear", None) => Ok(Activation::Linear),
+            ("softmax", None) => Ok(Activation::Softmax),
+            _ => Err(format!("Unknown activation '{}'", s)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ACTIVATIONS: [Activation; 8] = [
+        Activation::Sigmoid,
+        Activation::Tanh,
+        Activation::Relu,
+        Activation::LeakyRelu { slope: 0.1 },
+        Activation::Elu { alpha: 0.5 },
+        Activation::Softsign,
+        Activation::Linear,
+        Activation::Softmax,
+    ];
+
+    fn applied(activation: Activation, values: &[f64]) -> Vec<f64> {
+        let mut values = values.to_vec();
+        activation.apply(&mut values);
+
+        values
+    }
+
+    #[test]
+    fn piecewise_activations_treat_negatives_differently() {
+        let values = [-2.0, 0.0, 3.0];
+
+        assert_eq!(applied(Activation::Relu, &values), [0.0, 0.0, 3.0]);
+        assert_eq!(
+            applied(Activation::LeakyRelu { slope: 0.1 }, &values),
+            [-0.2, 0.0, 3.0]
+        );
+        assert_eq!(
+            applied(Activation::Elu { alpha: 0.5 }, &values),
+            [0.5 * (-2.0f64).exp_m1(), 0.0, 3.0]
+        );
+        assert_eq!(
+            applied(Activation::Softsign, &values),
+            [-2.0 / 3.0, 0.0, 0.75]
+        );
+        assert_eq!(applied(Activation::Linear, &values), values);
+    }
+
+    #[test]
+    fn softmax_sums_to_one_without_overflowing() {
+        let small = applied(Activation::Softmax, &[0.0, 1.0, 2.0]);
+        assert!((small.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+        assert!(small[0] < small[1] && small[1] < small[2]);
+
+        // exp(1000) overflows, but softmax only depends on the differences.
+        let large = applied(Activation::Softmax, &[1000.0, 1001.0, 1002.0]);
+        for (&large, &small) in large.iter().zip(small.iter()) {
+            assert!((large - small).abs() < 1e-12, "{:?}", large);
+        }
+    }
+
+    #[test]
+    fn bounded_activations_stay_in_their_range() {
+        let values = [-1e6, -3.0, -0.5, 0.0, 0.5, 3.0, 1e6];
+
+        for &activation in ACTIVATIONS.iter() {
+            match activation.range() {
+                Some((low, high)) => {
+                    for val in applied(activation, &values) {
+                        assert!(val >= low && val <= high, "{} gave {}", activation, val);
+                    }
+                }
+                None => assert!(applied(activation, &values).iter().any(|&val| val >= 1e6)),
+            }
+        }
+    }
+
+    #[test]
+    fn activations_round_trip() {
+        for &activation in ACTIVATIONS.iter() {
+            assert_eq!(activation.to_string().parse::<Activation>(), Ok(activation));
+
+            let mut bytes = Vec::new();
+            activation.write_to(&mut bytes).unwrap();
+            let read = Activation::read_from(&mut bytes.as_slice()).unwrap();
+            assert_eq!(read, activation);
+        }
+
+        assert!("leaky-relu:inf".parse::<Activation>().is_err());
+        assert!("swish".parse::<Activation>().is_err());
+        assert!(matches!(
+            Activation::read_from(&mut [8u8].as_ref()),
+            Err(FormatError::Invalid(_))
+        ));
+    }
+}
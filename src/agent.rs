@@ -1,18 +1,112 @@
 use rand::distributions::Standard;
-use rand::{thread_rng, Rng};
+use rand::Rng;
 
-use sdl2::render::Canvas;
-use sdl2::video::Window;
-
-use crate::network::Network;
+use crate::activation::Activation;
+use crate::format::{self, FormatError};
+use crate::network::{Crossover, Network, Workspace};
+use crate::render::Renderer;
+use crate::replay::Replay;
+use crate::rng::SimRng;
 use crate::snake;
 
+use std::io::{self, Read, Write};
 use std::time::Duration;
 
 pub trait Agent: Clone {
-    fn fitness(&self) -> f64;
-    fn crossover(&self, other: &Self) -> Self;
-    fn mutate(&self) -> Self;
+    /// Scores the agent. Any randomness in the evaluation is drawn from
+    /// `seed`, so the same agent and seed always give the same result.
+    fn evaluate(&self, seed: u64) -> Evaluation;
+    /// Breeds a child from the agent and `other`, using `operator` where the
+    /// agent's genes suit it.
+    fn crossover(&self, other: &Self, operator: Crossover, rng: &mut SimRng) -> Self;
+    fn mutate(&self, rng: &mut SimRng) -> Self;
+    /// Every value that makes up the agent, used to measure how much a
+    /// population varies. Agents of the same kind give genomes of the same length.
+    fn genome(&self) -> Vec<f64>;
+
+    fn fitness(&self, seed: u64) -> f64 {
+        self.evaluate(seed).fitness
+    }
+}
+
+/// How an agent did when it was evaluated.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Evaluation {
+    pub fitness: f64,
+    /// Best score reached in any of the games played, for agents that play
+    /// games.
+    pub game_score: Option<u32>,
+    /// Mean number of steps survived per game, for agents that play games.
+    pub steps_survived: Option<f64>,
+}
+
+impl Evaluation {
+    /// An evaluation with nothing to report but the fitness.
+    pub fn from_fitness(fitness: f64) -> Self {
+        Self {
+            fitness,
+            game_score: None,
+            steps_survived: None,
+        }
+    }
+
+    // Written as the fitness (`f64`), then the game score and steps survived,
+    // each as a byte that's 1 if present followed by the value (`u32` and
+    // `f64`).
+    pub(crate) fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        format::write_f64(writer, self.fitness)?;
+        match self.game_score {
+            Some(game_score) => {
+                format::write_u8(writer, 1)?;
+                format::write_u32(writer, game_score)?;
+            }
+            None => format::write_u8(writer, 0)?,
+        }
+
+        match self.steps_survived {
+            Some(steps_survived) => {
+                format::write_u8(writer, 1)?;
+                format::write_f64(writer, steps_survived)
+            }
+            None => format::write_u8(writer, 0),
+        }
+    }
+
+    pub(crate) fn read_from(reader: &mut impl Read) -> Result<Self, FormatError> {
+        let fitness = format::read_f64(reader)?;
+        let game_score = match read_flag(reader, "game score")? {
+            true => Some(format::read_u32(reader)?),
+            false => None,
+        };
+        let steps_survived = match read_flag(reader, "steps survived")? {
+            true => Some(format::read_f64(reader)?),
+            false => None,
+        };
+
+        Ok(Self {
+            fitness,
+            game_score,
+            steps_survived,
+        })
+    }
+}
+
+fn read_flag(reader: &mut impl Read, name: &str) -> Result<bool, FormatError> {
+    match format::read_u8(reader)? {
+        0 => Ok(false),
+        1 => Ok(true),
+        other => Err(FormatError::Invalid(format!(
+            "{} is not a valid {} flag",
+            other, name
+        ))),
+    }
+}
+
+/// Agents that can be written out and read back, e.g. as part of a
+/// population checkpoint.
+pub trait Persist: Sized {
+    fn write_to(&self, writer: &mut impl Write) -> io::Result<()>;
+    fn read_from(reader: &mut impl Read) -> Result<Self, FormatError>;
 }
 
 #[derive(Clone)]
@@ -22,8 +116,7 @@ pub struct Binary {
 }
 
 impl Binary {
-    pub fn new(len: usize, mutation_prob: f64) -> Self {
-        let rng = thread_rng();
+    pub fn new(len: usize, mutation_prob: f64, rng: &mut SimRng) -> Self {
         let vals = rng.sample_iter(Standard).take(len).collect();
 
         Self {
@@ -34,15 +127,15 @@ impl Binary {
 }
 
 impl Agent for Binary {
-    fn fitness(&self) -> f64 {
+    fn evaluate(&self, _seed: u64) -> Evaluation {
         let prefitness = self.vals.iter().filter(|&&val| val).count() as f64;
 
-        f64::powi(prefitness, 40)
+        Evaluation::from_fitness(f64::powi(prefitness, 40))
     }
 
-    fn crossover(&self, other: &Self) -> Self {
-        let mut rng = thread_rng();
-
+    // Only uniform crossover makes sense for single bits, so `operator` is
+    // ignored.
+    fn crossover(&self, other: &Self, _operator: Crossover, rng: &mut SimRng) -> Self {
         // Choose either the first or second value with equal probability, then
         // mutate each element with a given probability.
         let new_vals: Vec<_> = self
@@ -61,9 +154,7 @@ impl Agent for Binary {
         }
     }
 
-    fn mutate(&self) -> Self {
-        let mut rng = thread_rng();
-
+    fn mutate(&self, rng: &mut SimRng) -> Self {
         let new_vals: Vec<_> = self
             .vals
             .iter()
@@ -75,29 +166,190 @@ impl Agent for Binary {
             mutation_prob: self.mutation_prob,
         }
     }
+
+    fn genome(&self) -> Vec<f64> {
+        self.vals
+            .iter()
+            .map(|&val| f64::from(u8::from(val)))
+            .collect()
+    }
+}
+
+// Written as the mutation probability (`f64`), the number of values (`u64`),
+// and then one byte per value.
+impl Persist for Binary {
+    fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        format::write_f64(writer, self.mutation_prob)?;
+        format::write_u64(writer, self.vals.len() as u64)?;
+        for &val in self.vals.iter() {
+            format::write_u8(writer, val.into())?;
+        }
+
+        Ok(())
+    }
+
+    fn read_from(reader: &mut impl Read) -> Result<Self, FormatError> {
+        let mutation_prob = format::read_f64(reader)?;
+        let len = format::read_usize(reader)?;
+        let vals = (0..len)
+            .map(|_| match format::read_u8(reader)? {
+                0 => Ok(false),
+                1 => Ok(true),
+                other => Err(FormatError::Invalid(format!(
+                    "{} is not a valid binary value",
+                    other
+                ))),
+            })
+            .collect::<Result<Vec<bool>, FormatError>>()?;
+
+        Ok(Self {
+            vals,
+            mutation_prob,
+        })
+    }
 }
 
 #[derive(Clone)]
 pub struct Snake {
     rules: Network,
+    game_config: snake::GameConfig,
 }
 
 impl Snake {
     const SNAKE_STEPS: usize = 50000;
+    const RUNS_TO_EVALUATE: u32 = 5;
     const NETWORK_INNER_LAYERS: [usize; 3] = [8, 8, 8];
 
-    pub fn new() -> Self {
+    /// Size in pixels of each node in the network view drawn by `render`.
+    pub const NETWORK_SCALE: u16 = 8;
+
+    const NETWORK_INPUTS: usize = 6;
+    const NETWORK_OUTPUTS: usize = 4; // one per direction
+
+    pub fn new(game_config: snake::GameConfig, rng: &mut SimRng) -> Self {
+        Self::with_activations(game_config, Activation::Sigmoid, Activation::Sigmoid, rng)
+    }
+
+    /// Same as `new`, but with `hidden` activation for the network's inner
+    /// layers and `output` for its last.
+    pub fn with_activations(
+        game_config: snake::GameConfig,
+        hidden: Activation,
+        output: Activation,
+        rng: &mut SimRng,
+    ) -> Self {
         let mut network_size = Snake::NETWORK_INNER_LAYERS.to_vec();
-        network_size.insert(0, 6); // 6 input values
-        network_size.push(4); // 4 output values
+        network_size.insert(0, Snake::NETWORK_INPUTS);
+        network_size.push(Snake::NETWORK_OUTPUTS);
+
+        let mut activations = vec![hidden; Snake::NETWORK_INNER_LAYERS.len()];
+        activations.push(output);
 
         Self {
-            rules: Network::new(network_size),
+            rules: Network::with_activations(network_size, activations, rng),
+            game_config,
         }
     }
 
-    fn get_next_press(&self, stats: snake::GameStats) -> snake::Direction {
-        let network_inputs = vec![
+    /// Wraps an existing network, e.g. one loaded from disk, making sure it
+    /// has the inputs and outputs a snake needs.
+    pub fn from_network(
+        game_config: snake::GameConfig,
+        rules: Network,
+    ) -> Result<Self, FormatError> {
+        rules.check_io(Snake::NETWORK_INPUTS, Snake::NETWORK_OUTPUTS)?;
+
+        Ok(Self { rules, game_config })
+    }
+
+    pub fn get_network(&self) -> &Network {
+        &self.rules
+    }
+
+    /// Seed of the game played for evaluation run `run` of a fitness call
+    /// made with `seed`.
+    pub fn run_seed(seed: u64, run: u32) -> u64 {
+        seed.wrapping_add(u64::from(run))
+    }
+
+    /// Records the games played when evaluating fitness with `seed`, along
+    /// with the score each one earned.
+    pub fn record_evaluation(&self, seed: u64) -> Vec<(Replay, f64)> {
+        let mut workspace = Workspace::new();
+        (0..Snake::RUNS_TO_EVALUATE)
+            .map(|run| {
+                let mut game =
+                    snake::Game::new_recorded(&self.game_config, Snake::run_seed(seed, run));
+                let evaluation = self.play(&mut game, &mut workspace);
+
+                (game.take_replay().unwrap(), evaluation.fitness)
+            })
+            .collect()
+    }
+
+    // Plays a game until it ends or runs out of steps. Its fitness is the
+    // snake's length, less how far it ended up from the food as a fraction
+    // of the board's diagonal.
+    fn play(&self, game: &mut snake::Game, workspace: &mut Workspace) -> Evaluation {
+        let mut score = 0;
+        let mut steps = 0;
+        let mut maybe_last_stats: Option<snake::GameStats> = None;
+        for _ in 0..Snake::SNAKE_STEPS {
+            if let snake::GameStatus::InProgress(stats) = game.step() {
+                steps += 1;
+                score = u32::max(score, stats.score);
+                let button_press = self.get_next_press(stats, workspace);
+                game.turn_snake(button_press);
+                maybe_last_stats = Some(stats);
+            } else {
+                break;
+            }
+        }
+
+        // The game can end on its very first step, e.g. if it spawned facing a wall.
+        let last_stats = match maybe_last_stats {
+            Some(last_stats) => last_stats,
+            None => {
+                return Evaluation {
+                    fitness: 0.0,
+                    game_score: Some(0),
+                    steps_survived: Some(0.0),
+                }
+            }
+        };
+
+        let distance_to_food = f64::sqrt(
+            (last_stats.distance_to_food_x * last_stats.distance_to_food_x)
+                + (last_stats.distance_to_food_y * last_stats.distance_to_food_y),
+        );
+        let max_distance_to_food =
+            f64::hypot(f64::from(game.get_width()), f64::from(game.get_height()));
+
+        Evaluation {
+            fitness: (score as f64) - (distance_to_food / max_distance_to_food),
+            game_score: Some(score),
+            steps_survived: Some(f64::from(steps)),
+        }
+    }
+
+    /// Size in pixels of the network view that `render` draws to the right of
+    /// the board.
+    pub fn get_network_view_size(&self) -> (u32, u32) {
+        self.rules.render_size(Snake::NETWORK_SCALE)
+    }
+
+    fn get_next_press(
+        &self,
+        stats: snake::GameStats,
+        workspace: &mut Workspace,
+    ) -> snake::Direction {
+        let inputs = Snake::network_inputs(stats);
+
+        Snake::choose_direction(self.rules.evaluate_into(&inputs, workspace))
+    }
+
+    fn network_inputs(stats: snake::GameStats) -> [f64; Snake::NETWORK_INPUTS] {
+        [
             stats.distance_to_food_x,
             stats.distance_to_food_y,
             stats.distance_to_obstacle_up,
@@ -105,12 +357,12 @@ impl Snake {
             stats.distance_to_obstacle_down,
             stats.distance_to_obstacle_left,
         ]
-        .into_iter()
-        .map(|val: f64| Network::sigmoid(val))
-        .collect();
+        .map(Network::sigmoid)
+    }
 
-        let network_result = self.rules.evaluate(network_inputs);
-        let (selected_choice, selected_value) = network_result
+    // The direction whose output is highest.
+    fn choose_direction(network_result: &[f64]) -> snake::Direction {
+        let (selected_choice, _) = network_result
             .iter()
             .copied()
             .enumerate()
@@ -126,66 +378,138 @@ impl Snake {
         }
     }
 
-    pub fn render(&self, canvas: &mut Canvas<Window>) {
-        let mut game = snake::Game::new(50, 50, 16);
-        for _ in 0..Snake::SNAKE_STEPS {
-            if let snake::GameStatus::InProgress(stats) = game.step() {
-                game.render(canvas);
-                let button_press = self.get_next_press(stats);
-                game.turn_snake(button_press);
-                canvas.present();
-                std::thread::sleep(Duration::new(0, 1_000_000_000u32 / (500 as u32)));
-            } else {
-                break;
+    /// Starts a game, seeded with `seed`, to be played one step at a time,
+    /// e.g. to show it off.
+    pub fn playthrough(&self, seed: u64) -> Playthrough {
+        Playthrough {
+            snake: self.clone(),
+            game: snake::Game::new(&self.game_config, seed),
+            workspace: Workspace::new(),
+            moved: false,
+            steps: 0,
+        }
+    }
+
+    /// Plays and draws a single game, seeded with `seed`, at `fps` frames per
+    /// second. See `Playthrough::render` for what gets drawn.
+    pub fn render(&self, renderer: &mut impl Renderer, tile_size: u16, seed: u64, fps: u16) {
+        let mut playthrough = self.playthrough(seed);
+        while playthrough.step() {
+            playthrough.render(renderer, tile_size);
+            renderer.present();
+            std::thread::sleep(Duration::new(0, 1_000_000_000u32 / u32::from(fps)));
+        }
+    }
+}
+
+/// A game played by a snake one step at a time, see `Snake::playthrough`.
+pub struct Playthrough {
+    snake: Snake,
+    game: snake::Game,
+    // Holds the values of every layer of the network from when it picked
+    // the last move, if it's moved yet.
+    workspace: Workspace,
+    moved: bool,
+    steps: usize,
+}
+
+impl Playthrough {
+    /// Advances the game a step and lets the snake pick its next move.
+    /// Returns false once the game is over or has run out of steps, the same
+    /// as when evaluating fitness.
+    pub fn step(&mut self) -> bool {
+        if self.steps >= Snake::SNAKE_STEPS {
+            return false;
+        }
+
+        match self.game.step() {
+            snake::GameStatus::InProgress(stats) => {
+                let button_press = self.snake.get_next_press(stats, &mut self.workspace);
+                self.game.turn_snake(button_press);
+
+                self.moved = true;
+                self.steps += 1;
+                true
             }
+            snake::GameStatus::Over => false,
         }
     }
+
+    pub fn get_snake(&self) -> &Snake {
+        &self.snake
+    }
+
+    pub fn get_game(&self) -> &snake::Game {
+        &self.game
+    }
+
+    /// Draws the board, with the network to its right showing how strongly
+    /// each node fired as the snake picked its last move. Doesn't present
+    /// the frame, so more can be drawn on top.
+    pub fn render(&self, renderer: &mut impl Renderer, tile_size: u16) {
+        self.game.render(renderer, tile_size);
+        self.snake.rules.render(
+            renderer,
+            i32::from(self.game.get_width()) * i32::from(tile_size),
+            0,
+            Snake::NETWORK_SCALE,
+            Some(&self.workspace).filter(|_| self.moved),
+        );
+    }
 }
 
 impl Agent for Snake {
-    fn fitness(&self) -> f64 {
-        let mut total_score = 0.0;
-        let runs_to_evaluate = 5;
-        for _ in 0..runs_to_evaluate {
-            let mut game = snake::Game::new(50, 50, 0);
-            let mut score = 0;
-            let mut maybe_last_stats: Option<snake::GameStats> = None;
-            for step in 0..Snake::SNAKE_STEPS {
-                if let snake::GameStatus::InProgress(stats) = game.step() {
-                    score = u32::max(score, stats.score);
-                    let button_press = self.get_next_press(stats);
-                    game.turn_snake(button_press);
-                    maybe_last_stats = Some(stats);
-                } else {
-                    break;
-                }
-            }
+    fn evaluate(&self, seed: u64) -> Evaluation {
+        // Shared by every game, so the network allocates nothing as they play.
+        let mut workspace = Workspace::new();
+        let runs = (0..Snake::RUNS_TO_EVALUATE)
+            .map(|run| {
+                let mut game = snake::Game::new(&self.game_config, Snake::run_seed(seed, run));
+                self.play(&mut game, &mut workspace)
+            })
+            .collect::<Vec<Evaluation>>();
 
-            let last_stats = maybe_last_stats.unwrap();
-            let distance_to_food = f64::sqrt(
-                (last_stats.distance_to_food_x * last_stats.distance_to_food_x)
-                    + (last_stats.distance_to_food_y * last_stats.distance_to_food_y),
-            );
-            let max_distance_to_food = f64::sqrt(
-                ((game.get_width() * game.get_width()) + (game.get_height() * game.get_height()))
-                    as f64,
-            );
+        let mean = |value: fn(&Evaluation) -> f64| {
+            runs.iter().map(value).sum::<f64>() / f64::from(Snake::RUNS_TO_EVALUATE)
+        };
 
-            total_score += (score as f64) - (distance_to_food / max_distance_to_food);
+        Evaluation {
+            fitness: mean(|run| run.fitness),
+            game_score: runs.iter().filter_map(|run| run.game_score).max(),
+            steps_survived: Some(mean(|run| run.steps_survived.unwrap())),
         }
-
-        total_score / (f64::from(runs_to_evaluate))
     }
 
-    fn crossover(&self, other: &Self) -> Self {
+    fn crossover(&self, other: &Self, operator: Crossover, rng: &mut SimRng) -> Self {
         Self {
-            rules: self.rules.merge(&other.rules),
+            rules: self.rules.crossover(&other.rules, operator, rng),
+            game_config: self.game_config,
         }
     }
 
-    fn mutate(&self) -> Self {
+    fn mutate(&self, rng: &mut SimRng) -> Self {
         Self {
-            rules: self.rules.mutate(0.1, 3.0),
+            rules: self.rules.mutate(0.1, 3.0, rng),
+            game_config: self.game_config,
         }
     }
+
+    fn genome(&self) -> Vec<f64> {
+        self.rules.get_genes()
+    }
+}
+
+// Written as the game config followed by the network.
+impl Persist for Snake {
+    fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        self.game_config.write_to(writer)?;
+        self.rules.write_to(writer)
+    }
+
+    fn read_from(reader: &mut impl Read) -> Result<Self, FormatError> {
+        let game_config = snake::GameConfig::read_from(reader)?;
+        let rules = Network::read_from(reader)?;
+
+        Snake::from_network(game_config, rules)
+    }
 }
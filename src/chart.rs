@@ -0,0 +1,230 @@
+use crate::render::{self, Color, Renderer};
+use crate::stats::GenerationStats;
+
+// Picks the value of one line on the chart out of a generation's stats.
+type Series = fn(&GenerationStats) -> f64;
+
+/// A panel plotting the best and mean fitness of every generation so far,
+/// beneath the latest generation's number and scores.
+#[derive(Clone, Debug, Default)]
+pub struct FitnessChart {
+    history: Vec<GenerationStats>,
+}
+
+impl FitnessChart {
+    const BACKGROUND_COLOR: Color = Color::rgb(24, 24, 24);
+    const AXIS_COLOR: Color = Color::rgb(96, 96, 96);
+    const BEST_COLOR: Color = Color::rgb(0, 255, 0);
+    const MEAN_COLOR: Color = Color::rgb(255, 200, 0);
+
+    const TEXT_BLOCK_SIZE: u32 = 2;
+    const MARGIN: i32 = 8;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, stats: GenerationStats) {
+        self.history.push(stats);
+    }
+
+    pub fn get_history(&self) -> &[GenerationStats] {
+        &self.history
+    }
+
+    /// Draws the panel over the `width` by `height` rectangle whose top left
+    /// corner is at (`x`, `y`).
+    pub fn render(&self, renderer: &mut impl Renderer, x: i32, y: i32, width: u32, height: u32) {
+        renderer.fill_rect(x, y, width, height, Self::BACKGROUND_COLOR);
+
+        let block = Self::TEXT_BLOCK_SIZE;
+        let line_height = 7 * block as i32;
+        let mut lines: Vec<(String, Color)> = Vec::new();
+        if let Some(latest) = self.history.last() {
+            lines.push((format!("GEN {}", latest.generation), Color::WHITE));
+            lines.push((format!("BEST {:.2}", latest.best_fitness), Self::BEST_COLOR));
+            lines.push((format!("MEAN {:.2}", latest.mean_fitness), Self::MEAN_COLOR));
+            if let Some(score) = latest.best_game_score {
+                lines.push((format!("SCORE {}", score), Color::WHITE));
+            }
+        }
+
+        for (i, (text, color)) in lines.iter().enumerate() {
+            render::draw_text(
+                renderer,
+                text,
+                x + Self::MARGIN,
+                y + Self::MARGIN + (i as i32) * line_height,
+                block,
+                *color,
+            );
+        }
+
+        let left = x + Self::MARGIN;
+        let right = x + width as i32 - Self::MARGIN;
+        let top = y + 2 * Self::MARGIN + (lines.len() as i32) * line_height;
+        let bottom = y + height as i32 - Self::MARGIN;
+        if right - left < 2 || bottom - top < 2 {
+            return;
+        }
+
+        renderer.draw_line((left, top), (left, bottom), Self::AXIS_COLOR);
+        renderer.draw_line((left, bottom), (right, bottom), Self::AXIS_COLOR);
+        if self.history.is_empty() {
+            return;
+        }
+
+        let values = self
+            .history
+            .iter()
+            .flat_map(|stats| [stats.best_fitness, stats.mean_fitness]);
+        let min = values.clone().fold(f64::INFINITY, f64::min);
+        let max = values.fold(f64::NEG_INFINITY, f64::max);
+        // A flat line still needs some range to be drawn across.
+        let range = if max > min { max - min } else { 1.0 };
+
+        let last = usize::max(self.history.len() - 1, 1) as f64;
+        let point = |generation: usize, value: f64| {
+            (
+                left + ((generation as f64) / last * f64::from(right - left)) as i32,
+                bottom - ((value - min) / range * f64::from(bottom - top)) as i32,
+            )
+        };
+
+        let series: [(Series, Color); 2] = [
+            (|stats| stats.mean_fitness, Self::MEAN_COLOR),
+            (|stats| stats.best_fitness, Self::BEST_COLOR),
+        ];
+        for (value, color) in series.iter() {
+            if self.history.len() == 1 {
+                let (point_x, point_y) = point(0, value(&self.history[0]));
+                renderer.fill_rect(point_x - 1, point_y - 1, 3, 3, *color);
+                continue;
+            }
+
+            for (generation, pair) in self.history.windows(2).enumerate() {
+                renderer.draw_line(
+                    point(generation, value(&pair[0])),
+                    point(generation + 1, value(&pair[1])),
+                    *color,
+                );
+            }
+        }
+
+        // Label the top and bottom of the scale.
+        let label_x = left + Self::MARGIN / 2;
+        render::draw_text(
+            renderer,
+            &format!("{:.2}", max),
+            label_x,
+            top,
+            1,
+            Self::AXIS_COLOR,
+        );
+        render::draw_text(
+            renderer,
+            &format!("{:.2}", min),
+            label_x,
+            bottom - 7,
+            1,
+            Self::AXIS_COLOR,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::render::Framebuffer;
+
+    use std::time::Duration;
+
+    fn stats(generation: usize, best_fitness: f64, mean_fitness: f64) -> GenerationStats {
+        GenerationStats {
+            generation,
+            best_fitness,
+            mean_fitness,
+            median_fitness: mean_fitness,
+            worst_fitness: 0.0,
+            fitness_std_dev: 0.0,
+            best_game_score: None,
+            mean_steps_survived: None,
+            diversity: 0.0,
+            evaluation_time: Duration::ZERO,
+            breeding_time: Duration::ZERO,
+        }
+    }
+
+    // Every pixel of `color` at or below row `top`.
+    fn pixels_below(framebuffer: &Framebuffer, top: u32, color: Color) -> Vec<(u32, u32)> {
+        let mut pixels = Vec::new();
+        for y in top..framebuffer.get_height() {
+            for x in 0..framebuffer.get_width() {
+                if framebuffer.get_pixel(x, y) == color {
+                    pixels.push((x, y));
+                }
+            }
+        }
+
+        pixels
+    }
+
+    #[test]
+    fn an_empty_chart_only_draws_the_axes() {
+        let mut framebuffer = Framebuffer::new(100, 120);
+        FitnessChart::new().render(&mut framebuffer, 0, 0, 100, 120);
+
+        // With no text above it, the plot spans the whole panel inside the
+        // margins, from (8, 16) to (92, 112).
+        for y in 16..=112 {
+            assert_eq!(framebuffer.get_pixel(8, y), FitnessChart::AXIS_COLOR);
+        }
+        for x in 8..=92 {
+            assert_eq!(framebuffer.get_pixel(x, 112), FitnessChart::AXIS_COLOR);
+        }
+        assert_eq!(
+            framebuffer.get_pixel(50, 50),
+            FitnessChart::BACKGROUND_COLOR
+        );
+        for &color in [
+            Color::WHITE,
+            FitnessChart::BEST_COLOR,
+            FitnessChart::MEAN_COLOR,
+        ]
+        .iter()
+        {
+            assert!(pixels_below(&framebuffer, 0, color).is_empty());
+        }
+    }
+
+    #[test]
+    fn each_generation_is_plotted_across_the_panel() {
+        let mut chart = FitnessChart::new();
+        chart.push(stats(1, 1.0, 0.0));
+        chart.push(stats(2, 3.0, 1.0));
+        chart.push(stats(3, 2.0, 1.0));
+
+        let mut framebuffer = Framebuffer::new(100, 120);
+        chart.render(&mut framebuffer, 0, 0, 100, 120);
+
+        // Three lines of text push the plot down to rows 58 to 112, so each
+        // unit of fitness from 0 to 3 is 18 pixels, and generations are 42
+        // pixels apart from column 8.
+        let best = pixels_below(&framebuffer, 58, FitnessChart::BEST_COLOR);
+        for point in [(8, 94), (50, 58), (71, 67), (92, 76)].iter() {
+            assert!(best.contains(point), "{:?}", point);
+        }
+        assert!(best
+            .iter()
+            .all(|&(x, y)| (8..=92).contains(&x) && (58..=94).contains(&y)));
+
+        let mean = pixels_below(&framebuffer, 58, FitnessChart::MEAN_COLOR);
+        for point in [(8, 112), (29, 103), (50, 94), (92, 94)].iter() {
+            assert!(mean.contains(point), "{:?}", point);
+        }
+        assert!(mean
+            .iter()
+            .all(|&(x, y)| (8..=92).contains(&x) && (94..=112).contains(&y)));
+    }
+}
@@ -0,0 +1,289 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use neural::{Activation, BreedingConfig, Crossover, GameConfig, SelectionStrategy, TrainOptions};
+
+pub const USAGE: &str = "\
+Usage: neural <command> [options]
+
+Commands:
+    train     Evolve a population of snakes
+    watch     Load a saved network and watch it play
+    play      Play the game yourself with the arrow keys
+    replay    Play back a recorded game
+
+Board and display options (all commands):
+    --width <tiles>            Board width [default: 50]
+    --height <tiles>           Board height [default: 50]
+    --scale <pixels>           Size of a tile on screen [default: 16]
+    --fps <frames>             Frames per second [default: 10 for play, 500 otherwise]
+
+train options:
+    --population <size>        Number of agents [default: 5000]
+    --generations <count>      Stop after this many generations [default: never]
+    --seed <seed>              Seed for the whole run [default: 0]
+    --output <dir>             Where stats and checkpoints go [default: training]
+    --checkpoint-every <count> Generations between checkpoints [default: 10]
+    --selection <strategy>     How parents are picked: truncation[:<fraction>],
+                               tournament:<size>, roulette, rank or sus
+                               [default: truncation:0.1]
+    --crossover-rate <rate>    Chance a child has two parents [default: 0.5]
+    --crossover-operator <op>  How two parents are crossed: uniform, neuron,
+                               single-point, two-point, blend[:<alpha>] or
+                               sbx[:<eta>] [default: uniform]
+    --elites <count>           Fittest agents kept unchanged each generation [default: 1]
+    --hall-of-fame <size>      Best agents ever seen to remember [default: 10]
+    --breed-hall-of-fame       Let the hall of fame be picked as parents
+    --hidden-activation <act>  Activation of the network's inner layers: sigmoid,
+                               tanh, relu, leaky-relu[:<slope>], elu[:<alpha>],
+                               softsign or linear [default: sigmoid]
+    --output-activation <act>  Activation of the network's output layer, any of
+                               the above or softmax [default: sigmoid]
+    --resume                   Carry on from the checkpoint in the output directory
+    --headless                 Don't show the best snake after each generation
+
+watch options:
+    --network <file>           Network to load (required)
+    --seed <seed>              Seed of the game to play [default: 0]
+
+play options:
+    --seed <seed>              Seed of the game to play [default: 0]
+
+replay options:
+    --replay <file>            Recording to play back (required)
+
+Keys while watching a snake or a replay:
+    Space                      Pause or resume
+    . or Right                 Step a single frame while paused
+    + or Up                    Double the speed
+    - or Down                  Halve the speed
+    R                          Restart, with a new seed where there is one
+    N                          Skip to the next game, or generation when training
+    Esc or Q                   Quit
+";
+
+/// How games get shown on screen.
+#[derive(Clone, Copy, Debug)]
+pub struct DisplayOptions {
+    pub fps: u16,
+    pub scale: u16,
+}
+
+#[derive(Debug)]
+pub enum Command {
+    Train {
+        options: TrainOptions,
+        display: DisplayOptions,
+        headless: bool,
+    },
+    Watch {
+        network: PathBuf,
+        game_config: GameConfig,
+        seed: u64,
+        display: DisplayOptions,
+    },
+    Play {
+        game_config: GameConfig,
+        seed: u64,
+        display: DisplayOptions,
+    },
+    Replay {
+        replay: PathBuf,
+        display: DisplayOptions,
+    },
+    Help,
+}
+
+/// Parses the command line, not including the program name.
+pub fn parse<I>(args: I) -> Result<Command, String>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut args = args.into_iter();
+    let command = match args.next() {
+        Some(command) => command,
+        None => return Ok(Command::Help),
+    };
+
+    if matches!(command.as_str(), "help" | "--help" | "-h") {
+        return Ok(Command::Help);
+    }
+
+    let mut flags = Flags::parse(args)?;
+    if flags.take_switch("help")? {
+        return Ok(Command::Help);
+    }
+
+    let default_fps = if command == "play" { 10 } else { 500 };
+    let game_config = GameConfig::sized(
+        flags.take_value("width")?.unwrap_or(50),
+        flags.take_value("height")?.unwrap_or(50),
+    );
+    game_config
+        .validate()
+        .map_err(|err| format!("Invalid --width or --height: {}", err))?;
+
+    let display = DisplayOptions {
+        fps: flags.take_value("fps")?.unwrap_or(default_fps),
+        scale: flags.take_value("scale")?.unwrap_or(16),
+    };
+
+    if display.fps == 0 {
+        return Err("--fps must be at least 1".to_string());
+    }
+
+    let command = match command.as_str() {
+        "train" => {
+            let defaults = TrainOptions::default();
+            let options = TrainOptions {
+                population_size: flags
+                    .take_value("population")?
+                    .unwrap_or(defaults.population_size),
+                generations: flags.take_value("generations")?,
+                seed: flags.take_value("seed")?.unwrap_or(defaults.seed),
+                game_config,
+                output_dir: flags.take_value("output")?.unwrap_or(defaults.output_dir),
+                checkpoint_every: flags
+                    .take_value("checkpoint-every")?
+                    .unwrap_or(defaults.checkpoint_every),
+                breeding: BreedingConfig {
+                    selection: flags
+                        .take_value::<SelectionStrategy>("selection")?
+                        .unwrap_or(defaults.breeding.selection),
+                    crossover_rate: flags
+                        .take_value("crossover-rate")?
+                        .unwrap_or(defaults.breeding.crossover_rate),
+                    crossover: flags
+                        .take_value::<Crossover>("crossover-operator")?
+                        .unwrap_or(defaults.breeding.crossover),
+                    elite_count: flags
+                        .take_value("elites")?
+                        .unwrap_or(defaults.breeding.elite_count),
+                    hall_of_fame_size: flags
+                        .take_value("hall-of-fame")?
+                        .unwrap_or(defaults.breeding.hall_of_fame_size),
+                    breed_from_hall_of_fame: flags.take_switch("breed-hall-of-fame")?,
+                },
+                hidden_activation: flags
+                    .take_value::<Activation>("hidden-activation")?
+                    .unwrap_or(defaults.hidden_activation),
+                output_activation: flags
+                    .take_value::<Activation>("output-activation")?
+                    .unwrap_or(defaults.output_activation),
+                resume: flags.take_switch("resume")?,
+            };
+
+            if options.population_size < 2 {
+                return Err("--population must be at least 2".to_string());
+            }
+
+            if !(0.0..=1.0).contains(&options.breeding.crossover_rate) {
+                return Err("--crossover-rate must be between 0 and 1".to_string());
+            }
+
+            if options.breeding.elite_count >= options.population_size {
+                return Err("--elites must be less than --population".to_string());
+            }
+
+            if options.hidden_activation == Activation::Softmax {
+                return Err("--hidden-activation can't be softmax".to_string());
+            }
+
+            if options.checkpoint_every == 0 {
+                return Err("--checkpoint-every must be at least 1".to_string());
+            }
+
+            Command::Train {
+                options,
+                display,
+                headless: flags.take_switch("headless")?,
+            }
+        }
+        "watch" => Command::Watch {
+            network: flags
+                .take_value("network")?
+                .ok_or("watch needs a --network to load")?,
+            game_config,
+            seed: flags.take_value("seed")?.unwrap_or(0),
+            display,
+        },
+        "play" => Command::Play {
+            game_config,
+            seed: flags.take_value("seed")?.unwrap_or(0),
+            display,
+        },
+        "replay" => Command::Replay {
+            replay: flags
+                .take_value("replay")?
+                .ok_or("replay needs a --replay file to play back")?,
+            display,
+        },
+        other => return Err(format!("Unknown command '{}'", other)),
+    };
+
+    flags.finish()?;
+
+    Ok(command)
+}
+
+// Every flag given on the command line, in the form `--name value` or
+// `--name`. Flags are taken out as they're used, so any left over at the end
+// weren't recognised.
+struct Flags {
+    flags: Vec<(String, Option<String>)>,
+}
+
+impl Flags {
+    fn parse<I>(args: I) -> Result<Self, String>
+    where
+        I: Iterator<Item = String>,
+    {
+        let mut flags: Vec<(String, Option<String>)> = Vec::new();
+        for arg in args {
+            match arg.strip_prefix("--") {
+                Some(name) => flags.push((name.to_string(), None)),
+                None => match flags.last_mut() {
+                    Some((_, value @ None)) => *value = Some(arg),
+                    _ => return Err(format!("Unexpected argument '{}'", arg)),
+                },
+            }
+        }
+
+        Ok(Self { flags })
+    }
+
+    fn take(&mut self, name: &str) -> Option<Option<String>> {
+        let index = self.flags.iter().position(|(flag, _)| flag == name)?;
+
+        Some(self.flags.remove(index).1)
+    }
+
+    fn take_switch(&mut self, name: &str) -> Result<bool, String> {
+        match self.take(name) {
+            Some(None) => Ok(true),
+            Some(Some(value)) => Err(format!("--{} doesn't take a value, got '{}'", name, value)),
+            None => Ok(false),
+        }
+    }
+
+    fn take_value<T>(&mut self, name: &str) -> Result<Option<T>, String>
+    where
+        T: FromStr,
+    {
+        match self.take(name) {
+            Some(Some(value)) => value
+                .parse()
+                .map(Some)
+                .map_err(|_| format!("Invalid value '{}' for --{}", value, name)),
+            Some(None) => Err(format!("--{} needs a value", name)),
+            None => Ok(None),
+        }
+    }
+
+    fn finish(self) -> Result<(), String> {
+        match self.flags.first() {
+            Some((flag, _)) => Err(format!("Unknown option '{}'", flag)),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use std::thread;
+use std::time::{Duration, Instant};
+
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::EventPump;
+
+/// What the viewer asked for, beyond what `Controls` handles itself.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Action {
+    None,
+    Restart,
+    Skip,
+    Quit,
+}
+
+/// Pausing, stepping and speed for a game being shown, driven by the keys
+/// listed in `cli::USAGE`.
+pub struct Controls {
+    fps: u32,
+    paused: bool,
+    step_requested: bool,
+    next_frame: Instant,
+}
+
+impl Controls {
+    const MAX_FPS: u32 = 4000;
+    // Longest to go without checking for input, so the window stays responsive.
+    const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+    pub fn new(fps: u16) -> Self {
+        Self {
+            fps: u32::from(fps),
+            paused: false,
+            step_requested: false,
+            next_frame: Instant::now(),
+        }
+    }
+
+    /// Handles every pending event, returning the last action asked for.
+    pub fn poll(&mut self, event_pump: &mut EventPump) -> Action {
+        let mut action = Action::None;
+        for event in event_pump.poll_iter() {
+            let keycode = match event {
+                Event::Quit { .. } => return Action::Quit,
+                Event::KeyDown {
+                    keycode: Some(keycode),
+                    ..
+                } => keycode,
+                _ => continue,
+            };
+
+            match keycode {
+                Keycode::Space => self.paused = !self.paused,
+                Keycode::Period | Keycode::Right => {
+                    self.paused = true;
+                    self.step_requested = true;
+                }
+                Keycode::Plus | Keycode::Equals | Keycode::KpPlus | Keycode::Up => {
+                    self.fps = u32::min(self.fps * 2, Self::MAX_FPS);
+                }
+                Keycode::Minus | Keycode::KpMinus | Keycode::Down => {
+                    self.fps = u32::max(self.fps / 2, 1);
+                }
+                Keycode::R => action = Action::Restart,
+                Keycode::N => action = Action::Skip,
+                Keycode::Escape | Keycode::Q => return Action::Quit,
+                _ => {}
+            }
+        }
+
+        action
+    }
+
+    /// Whether the next frame should be shown now. If not, waits a little
+    /// first, so this can be called in a loop with `poll`.
+    pub fn frame_due(&mut self) -> bool {
+        if self.paused {
+            if self.step_requested {
+                self.step_requested = false;
+                return true;
+            }
+
+            thread::sleep(Self::POLL_INTERVAL);
+            return false;
+        }
+
+        let now = Instant::now();
+        if now < self.next_frame {
+            thread::sleep(Duration::min(self.next_frame - now, Self::POLL_INTERVAL));
+            return false;
+        }
+
+        // Carry on from when this frame was due, unless that's fallen well
+        // behind, e.g. after a pause.
+        let frame_time = Duration::from_secs(1) / self.fps;
+        if self.next_frame + frame_time < now {
+            self.next_frame = now;
+        }
+        self.next_frame += frame_time;
+
+        true
+    }
+}
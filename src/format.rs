@@ -0,0 +1,183 @@
+//! Building blocks shared by the on-disk formats. Every file starts with a
+//! four byte magic number identifying what it holds, followed by a `u32`
+//! format version. All numbers are little-endian.
+
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+#[derive(Debug)]
+pub enum FormatError {
+    Io(io::Error),
+    /// The file doesn't start with the magic number of the expected format.
+    BadMagic {
+        expected: [u8; 4],
+        found: [u8; 4],
+    },
+    /// The file was written by a newer version of the format.
+    UnsupportedVersion {
+        found: u32,
+        latest: u32,
+    },
+    /// A network doesn't have the number of inputs and outputs its user needs.
+    WrongShape {
+        expected_inputs: usize,
+        expected_outputs: usize,
+        found: Vec<usize>,
+    },
+    /// The file is in the right format, but what it holds makes no sense.
+    Invalid(String),
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FormatError::Io(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                write!(f, "file ended unexpectedly, it may be truncated")
+            }
+            FormatError::Io(err) => write!(f, "{}", err),
+            FormatError::BadMagic { expected, found } => write!(
+                f,
+                "expected file to start with {:?}, but it starts with {:?}",
+                String::from_utf8_lossy(expected),
+                String::from_utf8_lossy(found)
+            ),
+            FormatError::UnsupportedVersion { found, latest } => write!(
+                f,
+                "file is version {}, but only versions up to {} are supported",
+                found, latest
+            ),
+            FormatError::WrongShape {
+                expected_inputs,
+                expected_outputs,
+                found,
+            } => write!(
+                f,
+                "network has shape {:?}, but {} inputs and {} outputs are needed",
+                found, expected_inputs, expected_outputs
+            ),
+            FormatError::Invalid(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl Error for FormatError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FormatError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for FormatError {
+    fn from(err: io::Error) -> Self {
+        FormatError::Io(err)
+    }
+}
+
+pub fn write_header(writer: &mut impl Write, magic: &[u8; 4], version: u32) -> io::Result<()> {
+    writer.write_all(magic)?;
+    write_u32(writer, version)
+}
+
+/// Checks the magic number and returns the file's version, which must be at
+/// most `latest`.
+pub fn read_header(
+    reader: &mut impl Read,
+    magic: &[u8; 4],
+    latest: u32,
+) -> Result<u32, FormatError> {
+    let mut found = [0; 4];
+    reader.read_exact(&mut found)?;
+    if &found != magic {
+        return Err(FormatError::BadMagic {
+            expected: *magic,
+            found,
+        });
+    }
+
+    let version = read_u32(reader)?;
+    if version == 0 || version > latest {
+        return Err(FormatError::UnsupportedVersion {
+            found: version,
+            latest,
+        });
+    }
+
+    Ok(version)
+}
+
+pub fn write_u8(writer: &mut impl Write, val: u8) -> io::Result<()> {
+    writer.write_all(&[val])
+}
+
+pub fn write_u16(writer: &mut impl Write, val: u16) -> io::Result<()> {
+    writer.write_all(&val.to_le_bytes())
+}
+
+pub fn write_i16(writer: &mut impl Write, val: i16) -> io::Result<()> {
+    writer.write_all(&val.to_le_bytes())
+}
+
+pub fn write_u32(writer: &mut impl Write, val: u32) -> io::Result<()> {
+    writer.write_all(&val.to_le_bytes())
+}
+
+pub fn write_u64(writer: &mut impl Write, val: u64) -> io::Result<()> {
+    writer.write_all(&val.to_le_bytes())
+}
+
+pub fn write_f64(writer: &mut impl Write, val: f64) -> io::Result<()> {
+    writer.write_all(&val.to_le_bytes())
+}
+
+pub fn read_u8(reader: &mut impl Read) -> io::Result<u8> {
+    let mut bytes = [0; 1];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(bytes[0])
+}
+
+pub fn read_u16(reader: &mut impl Read) -> io::Result<u16> {
+    let mut bytes = [0; 2];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(u16::from_le_bytes(bytes))
+}
+
+pub fn read_i16(reader: &mut impl Read) -> io::Result<i16> {
+    let mut bytes = [0; 2];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(i16::from_le_bytes(bytes))
+}
+
+pub fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(u32::from_le_bytes(bytes))
+}
+
+pub fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(u64::from_le_bytes(bytes))
+}
+
+pub fn read_f64(reader: &mut impl Read) -> io::Result<f64> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+
+    Ok(f64::from_le_bytes(bytes))
+}
+
+/// Reads a `u64` that's used as a size, making sure it fits in a `usize`.
+pub fn read_usize(reader: &mut impl Read) -> Result<usize, FormatError> {
+    let val = read_u64(reader)?;
+
+    usize::try_from(val).map_err(|_| FormatError::Invalid(format!("size {} is too large", val)))
+}
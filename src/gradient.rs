@@ -0,0 +1,350 @@
+//! Training a `Network` by gradient descent, as an alternative to evolving
+//! it. `Network::gradients` backpropagates a `Loss` over a batch of inputs,
+//! and a `Trainer` uses them to step the weights and biases with one of the
+//! `Optimiser`s.
+
+use crate::matrix::Matrix;
+use crate::network::Network;
+
+use std::fmt;
+use std::str::FromStr;
+
+/// How far a network's outputs are from their targets.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Loss {
+    /// The squared difference between each output and its target, averaged
+    /// over every output of every row.
+    MeanSquaredError,
+    /// `-target * ln(output)` summed over each row's outputs and averaged
+    /// over the rows. Targets should be probabilities summing to 1, e.g.
+    /// one-hot, and it pairs with a softmax output layer.
+    CrossEntropy,
+}
+
+impl Loss {
+    // Outputs are kept at least this far from zero when taking logs, so a
+    // confidently wrong output gives a large loss rather than infinity.
+    const MIN_PROBABILITY: f64 = 1e-12;
+
+    /// The loss of `outputs` against `targets`, which must be the same
+    /// shape.
+    pub fn loss(&self, outputs: &Matrix<f64>, targets: &Matrix<f64>) -> f64 {
+        Loss::check_shapes(outputs, targets);
+
+        let (rows, cols) = outputs.get_shape();
+        let mut total = 0.0;
+        for row in 0..rows {
+            for (&output, &target) in outputs[row].iter().zip(targets[row].iter()) {
+                total += match self {
+                    Loss::MeanSquaredError => (output - target).powi(2),
+                    Loss::CrossEntropy => -target * output.max(Loss::MIN_PROBABILITY).ln(),
+                };
+            }
+        }
+
+        match self {
+            Loss::MeanSquaredError => total / (rows * cols) as f64,
+            Loss::CrossEntropy => total / rows as f64,
+        }
+    }
+
+    /// The gradient of `loss` with respect to each output.
+    pub fn gradient(&self, outputs: &Matrix<f64>, targets: &Matrix<f64>) -> Matrix<f64> {
+        Loss::check_shapes(outputs, targets);
+
+        let (rows, cols) = outputs.get_shape();
+        Matrix::new_map(rows, cols, |row, col| {
+            let (output, target) = (outputs[row][col], targets[row][col]);
+            match self {
+                Loss::MeanSquaredError => 2.0 * (output - target) / (rows * cols) as f64,
+                Loss::CrossEntropy => -target / output.max(Loss::MIN_PROBABILITY) / rows as f64,
+            }
+        })
+    }
+
+    fn check_shapes(outputs: &Matrix<f64>, targets: &Matrix<f64>) {
+        if outputs.get_shape() != targets.get_shape() {
+            panic!(
+                "Cannot compare {}x{} outputs with {}x{} targets",
+                outputs.get_height(),
+                outputs.get_width(),
+                targets.get_height(),
+                targets.get_width()
+            );
+        }
+    }
+}
+
+/// Written the same way it's parsed, `mse` or `cross-entropy`.
+impl fmt::Display for Loss {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Loss::MeanSquaredError => write!(f, "mse"),
+            Loss::CrossEntropy => write!(f, "cross-entropy"),
+        }
+    }
+}
+
+impl FromStr for Loss {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "mse" => Ok(Loss::MeanSquaredError),
+            "cross-entropy" => Ok(Loss::CrossEntropy),
+            _ => Err(format!("Unknown loss '{}'", s)),
+        }
+    }
+}
+
+/// The gradient of a loss with respect to every weight and bias of a
+/// network, laid out the same way as the network's own.
+#[derive(Clone, Debug)]
+pub struct Gradients {
+    weights: Vec<Matrix<f64>>,
+    biases: Vec<Matrix<f64>>,
+}
+
+impl Gradients {
+    pub(crate) fn new(weights: Vec<Matrix<f64>>, biases: Vec<Matrix<f64>>) -> Self {
+        Self { weights, biases }
+    }
+
+    /// The gradient of the weights into layer `layer + 1`, one row per
+    /// neuron of layer `layer`.
+    pub fn get_weights(&self, layer: usize) -> &Matrix<f64> {
+        &self.weights[layer]
+    }
+
+    /// The gradient of the biases of layer `layer + 1`.
+    pub fn get_biases(&self, layer: usize) -> &Matrix<f64> {
+        &self.biases[layer]
+    }
+
+    // Every matrix, in the same order as `Network::parameters_mut`.
+    fn matrices(&self) -> impl Iterator<Item = &Matrix<f64>> {
+        self.weights.iter().chain(self.biases.iter())
+    }
+}
+
+/// How a `Trainer` turns gradients into changes to the weights and biases.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Optimiser {
+    /// Plain gradient descent, stepping each parameter by `learning_rate`
+    /// times its gradient.
+    Sgd { learning_rate: f64 },
+    /// Gradient descent where each step carries on `momentum` of the last,
+    /// which speeds it along shallow, consistent slopes.
+    Momentum { learning_rate: f64, momentum: f64 },
+    /// Adam: steps scaled per parameter by running averages of its gradient
+    /// and squared gradient, decaying by `beta1` and `beta2` respectively.
+    Adam {
+        learning_rate: f64,
+        beta1: f64,
+        beta2: f64,
+        epsilon: f64,
+    },
+}
+
+impl Optimiser {
+    /// Adam with the usual defaults for everything but the learning rate.
+    pub fn adam(learning_rate: f64) -> Self {
+        Optimiser::Adam {
+            learning_rate,
+            beta1: 0.9,
+            beta2: 0.999,
+            epsilon: 1e-8,
+        }
+    }
+}
+
+/// Trains a network on batches of examples, remembering whatever its
+/// optimiser needs between steps. Use one trainer per network.
+#[derive(Clone, Debug)]
+pub struct Trainer {
+    optimiser: Optimiser,
+    loss: Loss,
+    // Momentum's velocity, or Adam's average gradient, per parameter matrix.
+    first_moments: Vec<Matrix<f64>>,
+    // Adam's average squared gradient, per parameter matrix.
+    second_moments: Vec<Matrix<f64>>,
+    steps: i32,
+}
+
+impl Trainer {
+    pub fn new(optimiser: Optimiser, loss: Loss) -> Self {
+        Self {
+            optimiser,
+            loss,
+            first_moments: Vec::new(),
+            second_moments: Vec::new(),
+            steps: 0,
+        }
+    }
+
+    pub fn get_optimiser(&self) -> Optimiser {
+        self.optimiser
+    }
+
+    pub fn get_loss(&self) -> Loss {
+        self.loss
+    }
+
+    /// Takes one step on a batch of examples, one per row of `inputs` and
+    /// `targets`, returning the loss from before the step. The batch mustn't
+    /// be empty.
+    pub fn train_batch(
+        &mut self,
+        network: &mut Network,
+        inputs: &Matrix<f64>,
+        targets: &Matrix<f64>,
+    ) -> f64 {
+        let (loss, gradients) = network.gradients(inputs, targets, self.loss);
+        self.step(network, &gradients);
+
+        loss
+    }
+
+    /// Steps `network` against `gradients`, which must have come from a
+    /// network of the same shape.
+    pub fn step(&mut self, network: &mut Network, gradients: &Gradients) {
+        self.fit(gradients);
+        self.steps += 1;
+
+        let parameters = network.parameters_mut().collect::<Vec<&mut Matrix<f64>>>();
+        if parameters.len() != self.first_moments.len() {
+            panic!(
+                "Cannot step a network with {} parameter matrices using gradients for {}",
+                parameters.len(),
+                self.first_moments.len()
+            );
+        }
+
+        let state = self
+            .first_moments
+            .iter_mut()
+            .zip(self.second_moments.iter_mut());
+        for ((parameter, gradient), (first, second)) in
+            parameters.into_iter().zip(gradients.matrices()).zip(state)
+        {
+            if parameter.get_shape() != gradient.get_shape() {
+                panic!(
+                    "Cannot step a {}x{} parameter with a {}x{} gradient",
+                    parameter.get_height(),
+                    parameter.get_width(),
+                    gradient.get_height(),
+                    gradient.get_width()
+                );
+            }
+
+            for row in 0..parameter.get_height() {
+                for col in 0..parameter.get_width() {
+                    let grad = gradient[row][col];
+                    let first = &mut first[row][col];
+                    let second = &mut second[row][col];
+
+                    parameter[row][col] += match self.optimiser {
+                        Optimiser::Sgd { learning_rate } => -learning_rate * grad,
+                        Optimiser::Momentum {
+                            learning_rate,
+                            momentum,
+                        } => {
+                            *first = momentum * *first - learning_rate * grad;
+                            *first
+                        }
+                        Optimiser::Adam {
+                            learning_rate,
+                            beta1,
+                            beta2,
+                            epsilon,
+                        } => {
+                            *first = beta1 * *first + (1.0 - beta1) * grad;
+                            *second = beta2 * *second + (1.0 - beta2) * grad * grad;
+
+                            // The averages start at zero, so correct for the
+                            // bias towards it over the first few steps.
+                            let first = *first / (1.0 - beta1.powi(self.steps));
+                            let second = *second / (1.0 - beta2.powi(self.steps));
+                            -learning_rate * first / (second.sqrt() + epsilon)
+                        }
+                    };
+                }
+            }
+        }
+    }
+
+    // Starts the optimiser state afresh if it isn't shaped for `gradients`.
+    fn fit(&mut self, gradients: &Gradients) {
+        let fits = self.first_moments.len() == gradients.weights.len() + gradients.biases.len()
+            && self
+                .first_moments
+                .iter()
+                .zip(gradients.matrices())
+                .all(|(moment, gradient)| moment.get_shape() == gradient.get_shape());
+
+        if !fits {
+            let zeroed = || {
+                gradients
+                    .matrices()
+                    .map(|gradient| Matrix::new(gradient.get_height(), gradient.get_width()))
+                    .collect::<Vec<Matrix<f64>>>()
+            };
+            self.first_moments = zeroed();
+            self.second_moments = zeroed();
+            self.steps = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::rng;
+
+    // Trains a small network on XOR, returning the loss before and after.
+    fn train_xor(optimiser: Optimiser) -> (f64, f64) {
+        let mut network = Network::new(vec![2, 4, 1], &mut rng::from_seed(1));
+        let inputs = Matrix::new_map(4, 2, |row, col| ((row >> col) & 1) as f64);
+        let targets = Matrix::new_map(4, 1, |row, _| ((row & 1) ^ (row >> 1)) as f64);
+
+        let mut trainer = Trainer::new(optimiser, Loss::MeanSquaredError);
+        let initial = trainer.train_batch(&mut network, &inputs, &targets);
+        for _ in 0..2000 {
+            trainer.train_batch(&mut network, &inputs, &targets);
+        }
+
+        let last = Loss::MeanSquaredError.loss(&network.evaluate_batch(&inputs), &targets);
+        (initial, last)
+    }
+
+    fn check_learns_xor(optimiser: Optimiser) {
+        let (initial, last) = train_xor(optimiser);
+
+        assert!(
+            last < 0.01 && last < initial / 10.0,
+            "{:?} only took the loss from {} to {}",
+            optimiser,
+            initial,
+            last
+        );
+    }
+
+    #[test]
+    fn sgd_learns_xor() {
+        check_learns_xor(Optimiser::Sgd { learning_rate: 2.0 });
+    }
+
+    #[test]
+    fn momentum_learns_xor() {
+        check_learns_xor(Optimiser::Momentum {
+            learning_rate: 0.5,
+            momentum: 0.9,
+        });
+    }
+
+    #[test]
+    fn adam_learns_xor() {
+        check_learns_xor(Optimiser::adam(0.05));
+    }
+}
@@ -0,0 +1,341 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, TryRecvError};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use sdl2::event::Event;
+use sdl2::keyboard::Keycode;
+use sdl2::render::Canvas;
+use sdl2::video::Window;
+use sdl2::EventPump;
+
+use neural::{
+    rng, Color, Direction, FitnessChart, FormatError, Game, GameConfig, GameStatus,
+    GenerationStats, Network, Playthrough, Renderer, Replay, Snake, TrainOptions,
+};
+
+use crate::cli::{Command, DisplayOptions};
+use crate::controls::{Action, Controls};
+
+// Width in pixels of the fitness chart shown beside the board while training.
+const CHART_WIDTH: u32 = 320;
+
+/// Runs a command that needs a window.
+pub fn run(command: Command) -> Result<(), String> {
+    match command {
+        Command::Train {
+            options, display, ..
+        } => train(options, &display),
+        Command::Watch {
+            network,
+            game_config,
+            seed,
+            display,
+        } => {
+            let snake = Network::load(&network)
+                .and_then(|rules| Snake::from_network(game_config, rules))
+                .map_err(|err| format!("Cannot load {}: {}", network.display(), err))?;
+            let (mut canvas, mut event_pump) =
+                open_window(&game_config, &display, snake.get_network_view_size())?;
+
+            // Keep playing fresh games until the window is closed.
+            let mut controls = Controls::new(display.fps);
+            let mut seed = seed;
+            let mut playthrough = snake.playthrough(seed);
+            loop {
+                match controls.poll(&mut event_pump) {
+                    Action::Quit => return Ok(()),
+                    Action::Restart | Action::Skip => {
+                        seed = seed.wrapping_add(1);
+                        playthrough = snake.playthrough(seed);
+                    }
+                    Action::None => {}
+                }
+
+                if controls.frame_due() {
+                    if !playthrough.step() {
+                        seed = seed.wrapping_add(1);
+                        playthrough = snake.playthrough(seed);
+                        continue;
+                    }
+
+                    playthrough.render(&mut canvas, display.scale);
+                    canvas.present();
+                }
+            }
+        }
+        Command::Play {
+            game_config,
+            seed,
+            display,
+        } => {
+            let (mut canvas, mut event_pump) = open_window(&game_config, &display, (0, 0))?;
+            play(&game_config, seed, &display, &mut canvas, &mut event_pump);
+
+            Ok(())
+        }
+        Command::Replay { replay, display } => {
+            let replay = Replay::load(&replay)
+                .map_err(|err| format!("Cannot load {}: {}", replay.display(), err))?;
+            let (mut canvas, mut event_pump) = open_window(replay.get_config(), &display, (0, 0))?;
+
+            let mut controls = Controls::new(display.fps);
+            let mut player = replay.play();
+            let mut score = 0;
+            loop {
+                match controls.poll(&mut event_pump) {
+                    Action::Quit | Action::Skip => break,
+                    Action::Restart => {
+                        player = replay.play();
+                        score = 0;
+                    }
+                    Action::None => {}
+                }
+
+                if controls.frame_due() {
+                    match player.step() {
+                        Some(GameStatus::InProgress(stats)) => score = stats.score,
+                        Some(GameStatus::Over) => {}
+                        None => break,
+                    }
+
+                    player.get_game().render(&mut canvas, display.scale);
+                    canvas.present();
+                }
+            }
+
+            println!("Final score: {}", score);
+            Ok(())
+        }
+        Command::Help => Ok(()),
+    }
+}
+
+// The best snake of a generation, as sent from the training thread.
+struct Champion {
+    stats: GenerationStats,
+    snake: Snake,
+    evaluation_seed: u64,
+}
+
+// Trains on a background thread, while this one shows off the latest
+// champion next to a chart of how training is going. A new champion takes
+// over once the current game ends, or straight away when skipping.
+fn train(options: TrainOptions, display: &DisplayOptions) -> Result<(), String> {
+    // Every snake trained has the same shape of network, so any one shows how
+    // much room its view needs.
+    let (network_width, network_height) =
+        Snake::new(options.game_config, &mut rng::from_seed(0)).get_network_view_size();
+    let (mut canvas, mut event_pump) = open_window(
+        &options.game_config,
+        display,
+        (network_width + CHART_WIDTH, network_height),
+    )?;
+    let (board_width, _) = board_size(&options.game_config, display);
+    let (_, window_height) = canvas.output_size()?;
+    let chart_x = (board_width + network_width) as i32;
+
+    let (sender, receiver) = mpsc::channel();
+    let stop = Arc::new(AtomicBool::new(false));
+    let mut trainer = {
+        let stop = Arc::clone(&stop);
+        Some(thread::spawn(move || {
+            neural::train::run_with(&options, |stats, population| {
+                let (best, _) = population.get_best().unwrap();
+                let champion = Champion {
+                    stats: *stats,
+                    snake: best.clone(),
+                    evaluation_seed: population.get_evaluation_seed(),
+                };
+
+                // If the window has gone, there's no one left to tell.
+                let _ = sender.send(champion);
+                !stop.load(Ordering::SeqCst) && !crate::interrupted()
+            })
+        }))
+    };
+
+    let mut chart = FitnessChart::new();
+    let mut controls = Controls::new(display.fps);
+    let mut latest: Option<Champion> = None;
+    // The game being shown, its seed, and the generation its snake is from.
+    let mut showing: Option<(Playthrough, u64, usize)> = None;
+    loop {
+        let action = controls.poll(&mut event_pump);
+        if action == Action::Quit || crate::interrupted() {
+            break;
+        }
+
+        loop {
+            match receiver.try_recv() {
+                Ok(champion) => {
+                    chart.push(champion.stats);
+                    latest = Some(champion);
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    // Training has finished by itself, so report any error
+                    // now, and otherwise keep showing the last champion.
+                    if let Some(trainer) = trainer.take() {
+                        finish_training(trainer)?;
+                    }
+                    break;
+                }
+            }
+        }
+
+        let champion = match latest.as_ref() {
+            Some(champion) => champion,
+            None => {
+                if controls.frame_due() {
+                    Renderer::clear(&mut canvas, Color::BLACK);
+                    chart.render(&mut canvas, chart_x, 0, CHART_WIDTH, window_height);
+                    canvas.present();
+                }
+                continue;
+            }
+        };
+
+        match action {
+            Action::Skip => showing = None,
+            Action::Restart => {
+                if let Some((playthrough, seed, _)) = showing.as_mut() {
+                    *seed = seed.wrapping_add(1);
+                    *playthrough = playthrough.get_snake().playthrough(*seed);
+                }
+            }
+            _ => {}
+        }
+
+        if showing.is_none() {
+            let seed = champion.evaluation_seed;
+            showing = Some((
+                champion.snake.playthrough(seed),
+                seed,
+                champion.stats.generation,
+            ));
+        }
+
+        if !controls.frame_due() {
+            continue;
+        }
+
+        let (playthrough, seed, generation) = showing.as_mut().unwrap();
+        if !playthrough.step() {
+            // Move on to the latest champion, or if there isn't a newer one,
+            // give this one a fresh game.
+            if champion.stats.generation == *generation {
+                *seed = seed.wrapping_add(1);
+                *playthrough = champion.snake.playthrough(*seed);
+            } else {
+                showing = None;
+            }
+            continue;
+        }
+
+        playthrough.render(&mut canvas, display.scale);
+        chart.render(&mut canvas, chart_x, 0, CHART_WIDTH, window_height);
+        canvas.present();
+    }
+
+    // Let training finish its generation and write a final checkpoint.
+    stop.store(true, Ordering::SeqCst);
+    match trainer {
+        Some(trainer) => {
+            // Ctrl-C has already said as much.
+            if !crate::interrupted() {
+                println!("Stopping after this generation");
+            }
+            finish_training(trainer)
+        }
+        None => Ok(()),
+    }
+}
+
+fn finish_training(trainer: JoinHandle<Result<(), FormatError>>) -> Result<(), String> {
+    trainer
+        .join()
+        .map_err(|_| "Training thread panicked".to_string())?
+        .map_err(|err| format!("Training failed: {}", err))
+}
+
+// Size in pixels of the board drawn by `Game::render`.
+fn board_size(game_config: &GameConfig, display: &DisplayOptions) -> (u32, u32) {
+    (
+        u32::from(game_config.width) * u32::from(display.scale),
+        u32::from(game_config.height) * u32::from(display.scale),
+    )
+}
+
+// Opens a window big enough for the board, plus a panel of the given width
+// and height to its right for anything shown alongside.
+fn open_window(
+    game_config: &GameConfig,
+    display: &DisplayOptions,
+    (panel_width, panel_height): (u32, u32),
+) -> Result<(Canvas<Window>, EventPump), String> {
+    let sdl_context = sdl2::init()?;
+    let video_subsystem = sdl_context.video()?;
+
+    let (board_width, board_height) = board_size(game_config, display);
+    let window = video_subsystem
+        .window(
+            "Snake Game",
+            board_width + panel_width,
+            u32::max(board_height, panel_height),
+        )
+        .position_centered()
+        .build()
+        .map_err(|err| err.to_string())?;
+
+    let canvas = window
+        .into_canvas()
+        .build()
+        .map_err(|err| err.to_string())?;
+    let event_pump = sdl_context.event_pump()?;
+
+    Ok((canvas, event_pump))
+}
+
+fn play(
+    game_config: &GameConfig,
+    seed: u64,
+    display: &DisplayOptions,
+    canvas: &mut Canvas<Window>,
+    event_pump: &mut EventPump,
+) {
+    let mut game = Game::new(game_config, seed);
+    let mut score = 0;
+    loop {
+        match game.step() {
+            GameStatus::InProgress(stats) => score = stats.score,
+            GameStatus::Over => {
+                println!("Game over, final score: {}", score);
+                return;
+            }
+        }
+
+        game.render(canvas, display.scale);
+        canvas.present();
+
+        for event in event_pump.poll_iter() {
+            match event {
+                Event::Quit { .. } => return,
+                Event::KeyDown {
+                    keycode: Some(pressed_keycode),
+                    ..
+                } => match pressed_keycode {
+                    Keycode::Up => game.turn_snake(Direction::Up),
+                    Keycode::Down => game.turn_snake(Direction::Down),
+                    Keycode::Left => game.turn_snake(Direction::Left),
+                    Keycode::Right => game.turn_snake(Direction::Right),
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+
+        std::thread::sleep(Duration::new(0, 1_000_000_000u32 / u32::from(display.fps)));
+    }
+}
@@ -0,0 +1,41 @@
+//! Evolving neural networks to play snake.
+//!
+//! The building blocks are usable on their own: [`Matrix`] and [`Network`]
+//! for the maths, [`Agent`] and [`Population`] for the genetic algorithm, and
+//! [`Game`] for the snake game the agents are scored on. Networks can also be
+//! trained by gradient descent with a [`Trainer`]. [`train`] ties them
+//! together into a headless training loop.
+
+pub mod activation;
+pub mod agent;
+pub mod chart;
+pub mod format;
+pub mod gradient;
+pub mod matrix;
+pub mod network;
+pub mod population;
+pub mod render;
+pub mod replay;
+pub mod rng;
+pub mod selection;
+pub mod snake;
+pub mod stats;
+pub mod train;
+
+pub use crate::activation::Activation;
+pub use crate::agent::{Agent, Binary, Evaluation, Persist, Playthrough, Snake};
+pub use crate::chart::FitnessChart;
+pub use crate::format::FormatError;
+pub use crate::gradient::{Gradients, Loss, Optimiser, Trainer};
+pub use crate::matrix::{Matrix, ShapeError};
+pub use crate::network::{Crossover, Network, Workspace};
+pub use crate::population::{BreedingConfig, Population};
+pub use crate::render::{Color, Framebuffer, Renderer};
+pub use crate::replay::{Replay, ReplayPlayer};
+pub use crate::rng::SimRng;
+pub use crate::selection::SelectionStrategy;
+pub use crate::snake::{
+    Direction, FoodPlacement, Game, GameConfig, GameStats, GameStatus, Location, Spawn,
+};
+pub use crate::stats::{CsvSink, GenerationStats, JsonLinesSink, StatsSink};
+pub use crate::train::TrainOptions;
@@ -1,89 +1,66 @@
-mod agent;
-mod matrix;
-mod network;
-mod population;
-mod snake;
+// Without a window most of what gets parsed has nowhere to go.
+#[cfg_attr(not(feature = "gui"), allow(dead_code))]
+mod cli;
+#[cfg(feature = "gui")]
+mod controls;
+#[cfg(feature = "gui")]
+mod gui;
 
-use std::time::Duration;
+use std::sync::atomic::{AtomicBool, Ordering};
 
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use crate::cli::Command;
 
-use crate::snake::{Direction, Game};
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
 
-use crate::network::Network;
-use crate::population::Population;
-
-const FPS: u16 = 10;
+/// Whether Ctrl-C has been pressed since `catch_interrupts` was called.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
 
-const GAME_WIDTH: u16 = 50;
-const GAME_HEIGHT: u16 = 50;
-const GAME_SCALE: u16 = 16;
-const NETWORK_SCALE: u16 = 20;
+// Lets training finish its generation and write a final checkpoint on
+// Ctrl-C, rather than being killed part way through.
+fn catch_interrupts() -> Result<(), String> {
+    ctrlc::set_handler(|| {
+        println!("Interrupted, stopping after this generation");
+        INTERRUPTED.store(true, Ordering::SeqCst);
+    })
+    .map_err(|err| format!("Cannot catch Ctrl-C: {}", err))
+}
 
 pub fn main() {
-    let sdl_context = sdl2::init().unwrap();
-    let video_subsystem = sdl_context.video().unwrap();
-
-    let window = video_subsystem
-        .window(
-            "Snake Game",
-            (GAME_WIDTH * GAME_SCALE).into(),
-            (GAME_HEIGHT * GAME_SCALE).into(),
-        )
-        .position_centered()
-        .build()
-        .unwrap();
-
-    let mut canvas = window.into_canvas().build().unwrap();
-
-    let mut event_pump = sdl_context.event_pump().unwrap();
-
-    let mut game = Game::new(GAME_WIDTH, GAME_HEIGHT, GAME_SCALE);
-    let mut population = Population::new((0..5_000).map(|_| agent::Snake::new()).collect());
-
-    let mut generation = 1;
-    loop {
-        population = population.breed();
-        let (best, best_score) = population.get_best();
-        println!("Best score of generation {}: {}", generation, best_score);
-        best.render(&mut canvas);
-        for event in event_pump.poll_iter() {
-            match event {
-                Event::Quit { .. } => return,
-                _ => {}
-            };
+    let command = match cli::parse(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(err) => {
+            eprintln!("{}\n\n{}", err, cli::USAGE);
+            std::process::exit(2);
         }
+    };
 
-        generation += 1;
+    if let Err(err) = run(command) {
+        eprintln!("{}", err);
+        std::process::exit(1);
     }
+}
 
-    return;
-
-    let mut i = 0;
-    loop {
-        println!("{:?}", game.step());
-        game.render(&mut canvas);
-        //network.render(&mut canvas, NETWORK_SCALE);
-        canvas.present();
+fn run(command: Command) -> Result<(), String> {
+    if let Command::Train { .. } = command {
+        catch_interrupts()?;
+    }
 
-        for event in event_pump.poll_iter() {
-            match event {
-                Event::Quit { .. } => return,
-                Event::KeyDown {
-                    keycode: Some(pressed_keycode),
-                    ..
-                } => match pressed_keycode {
-                    Keycode::Up => game.turn_snake(Direction::Up),
-                    Keycode::Down => game.turn_snake(Direction::Down),
-                    Keycode::Left => game.turn_snake(Direction::Left),
-                    Keycode::Right => game.turn_snake(Direction::Right),
-                    _ => {}
-                },
-                _ => {}
-            }
+    match command {
+        Command::Help => {
+            print!("{}", cli::USAGE);
+            Ok(())
         }
-
-        std::thread::sleep(Duration::new(0, 1_000_000_000u32 / (FPS as u32)));
+        Command::Train {
+            options, headless, ..
+        } if headless || cfg!(not(feature = "gui")) => {
+            neural::train::run_with(&options, |_, _| !interrupted())
+                .map_err(|err| format!("Training failed: {}", err))
+        }
+        #[cfg(feature = "gui")]
+        command => gui::run(command),
+        #[cfg(not(feature = "gui"))]
+        _ => Err("This command needs a window, rebuild with `--features gui`".to_string()),
     }
 }
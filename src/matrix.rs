@@ -1,6 +1,31 @@
-use std::fmt::Debug;
+use crate::format::{self, FormatError};
+
+use std::error::Error;
+use std::fmt::{self, Debug};
+use std::io::{self, Read, Write};
 use std::ops::{Add, AddAssign, Div, DivAssign, Index, IndexMut, Mul, MulAssign, Sub, SubAssign};
 
+/// Returned when two matrices don't have the right shapes for an operation.
+/// Shapes are given as (height, width).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ShapeError {
+    pub operation: &'static str,
+    pub lhs: (usize, usize),
+    pub rhs: (usize, usize),
+}
+
+impl fmt::Display for ShapeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "cannot {} a {}x{} matrix and a {}x{} matrix",
+            self.operation, self.lhs.0, self.lhs.1, self.rhs.0, self.rhs.1
+        )
+    }
+}
+
+impl Error for ShapeError {}
+
 #[derive(Clone, Debug)]
 pub struct Matrix<T> {
     width: usize,
@@ -29,24 +54,17 @@ impl<T> Matrix<T> {
     where
         F: FnMut(usize, usize) -> T,
     {
-        let mut options_matrix: Matrix<Option<T>> = Matrix::new(height, width);
-
+        let mut values = Vec::with_capacity(height * width);
         for row in 0..height {
             for col in 0..width {
-                options_matrix[row][col] = Some(func(row, col))
+                values.push(func(row, col));
             }
         }
 
-        let result_values = options_matrix
-            .values
-            .into_iter()
-            .map(|option_value: Option<T>| option_value.unwrap())
-            .collect::<Vec<T>>();
-
         Self {
             height,
             width,
-            values: result_values,
+            values,
         }
     }
 
@@ -59,6 +77,18 @@ impl<T> Matrix<T> {
         self
     }
 
+    /// Same as `map`, but changes the values where they are rather than
+    /// building a new matrix.
+    pub fn map_in_place<F>(&mut self, func: F)
+    where
+        F: Fn(T) -> T,
+        T: Copy,
+    {
+        self.values
+            .iter_mut()
+            .for_each(|val: &mut T| *val = func(*val));
+    }
+
     pub fn get_width(&self) -> usize {
         self.width
     }
@@ -66,6 +96,200 @@ impl<T> Matrix<T> {
     pub fn get_height(&self) -> usize {
         self.height
     }
+
+    /// The height and width.
+    pub fn get_shape(&self) -> (usize, usize) {
+        (self.height, self.width)
+    }
+
+    /// The matrix flipped over its diagonal, so rows become columns.
+    pub fn transpose(&self) -> Self
+    where
+        T: Copy,
+    {
+        Self::new_map(self.width, self.height, |row, col| self[col][row])
+    }
+
+    // Elementwise operations need both matrices to be the same shape.
+    fn check_same_shape<U>(
+        &self,
+        rhs: &Matrix<U>,
+        operation: &'static str,
+    ) -> Result<(), ShapeError> {
+        if self.height != rhs.height || self.width != rhs.width {
+            return Err(ShapeError {
+                operation,
+                lhs: self.get_shape(),
+                rhs: rhs.get_shape(),
+            });
+        }
+
+        Ok(())
+    }
+
+    fn check_can_multiply<U>(&self, rhs: &Matrix<U>) -> Result<(), ShapeError> {
+        if self.width != rhs.height {
+            return Err(ShapeError {
+                operation: "multiply",
+                lhs: self.get_shape(),
+                rhs: rhs.get_shape(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+impl<T> Matrix<T>
+where
+    T: Copy,
+{
+    /// Same as `&self + rhs`, but returns an error rather than panicking if
+    /// the shapes don't match.
+    pub fn checked_add(&self, rhs: &Matrix<T>) -> Result<Matrix<T>, ShapeError>
+    where
+        T: Add<T, Output = T>,
+    {
+        self.check_same_shape(rhs, "add")?;
+
+        Ok(self + rhs)
+    }
+
+    /// Same as `&self - rhs`, but returns an error rather than panicking if
+    /// the shapes don't match.
+    pub fn checked_sub(&self, rhs: &Matrix<T>) -> Result<Matrix<T>, ShapeError>
+    where
+        T: Sub<T, Output = T>,
+    {
+        self.check_same_shape(rhs, "subtract")?;
+
+        Ok(self - rhs)
+    }
+
+    /// Same as `&self * rhs`, but returns an error rather than panicking if
+    /// `self` isn't as wide as `rhs` is high.
+    pub fn checked_mul(&self, rhs: &Matrix<T>) -> Result<Matrix<T>, ShapeError>
+    where
+        T: Mul<T, Output = T> + Add<T, Output = T> + Default,
+    {
+        self.check_can_multiply(rhs)?;
+
+        Ok(self * rhs)
+    }
+
+    /// Same as `self * rhs`, but writes the product into `out` rather than
+    /// allocating a new matrix, so it can be reused across calls. `out` must
+    /// already be as high as `self` and as wide as `rhs`.
+    pub fn mul_into(&self, rhs: &Matrix<T>, out: &mut Matrix<T>)
+    where
+        T: Mul<T, Output = T> + Add<T, Output = T> + Default,
+    {
+        if let Err(err) = self.check_can_multiply(rhs) {
+            panic!("{}", err);
+        }
+
+        if out.height != self.height || out.width != rhs.width {
+            panic!(
+                "Product of a {}x{} matrix and a {}x{} matrix doesn't fit in a {}x{} matrix",
+                self.height, self.width, rhs.height, rhs.width, out.height, out.width
+            );
+        }
+
+        // With nothing to sum over, every value of the product is zero.
+        if self.width == 0 {
+            out.values.fill_with(Default::default);
+            return;
+        }
+
+        for row in 0..self.height {
+            let lhs_value = self[row][0];
+            out[row]
+                .iter_mut()
+                .zip(rhs[0].iter())
+                .for_each(|(out_value, &rhs_value)| *out_value = lhs_value * rhs_value);
+        }
+        self.add_remaining_terms(rhs, out);
+    }
+
+    // Adds every term of the product `self * rhs` but the first to `out`.
+    // Each row of `out` is built up by streaming through whole rows of `rhs`,
+    // rather than walking down its columns, which keeps memory access
+    // sequential. Callers start `out` from the first term rather than zero,
+    // which saves adding to zero, so `self` mustn't be empty.
+    fn add_remaining_terms(&self, rhs: &Matrix<T>, out: &mut Matrix<T>)
+    where
+        T: Mul<T, Output = T> + Add<T, Output = T>,
+    {
+        let width = out.width;
+        for (row, out_row) in out.values.chunks_exact_mut(width.max(1)).enumerate() {
+            for (i, &lhs_value) in self[row].iter().enumerate().skip(1) {
+                out_row
+                    .iter_mut()
+                    .zip(rhs[i].iter())
+                    .for_each(|(out_value, &rhs_value)| {
+                        *out_value = *out_value + lhs_value * rhs_value
+                    });
+            }
+        }
+    }
+
+    /// Same as `self += rhs`, but returns an error, leaving `self` as it
+    /// was, rather than panicking if the shapes don't match.
+    pub fn checked_add_assign(&mut self, rhs: &Matrix<T>) -> Result<(), ShapeError>
+    where
+        T: AddAssign<T>,
+    {
+        self.check_same_shape(rhs, "add")?;
+        *self += rhs;
+
+        Ok(())
+    }
+
+    /// Same as `self -= rhs`, but returns an error, leaving `self` as it
+    /// was, rather than panicking if the shapes don't match.
+    pub fn checked_sub_assign(&mut self, rhs: &Matrix<T>) -> Result<(), ShapeError>
+    where
+        T: SubAssign<T>,
+    {
+        self.check_same_shape(rhs, "subtract")?;
+        *self -= rhs;
+
+        Ok(())
+    }
+}
+
+impl Matrix<f64> {
+    /// Writes the height and width as `u64`s, followed by every value as an
+    /// `f64`, row by row.
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        format::write_u64(writer, self.height as u64)?;
+        format::write_u64(writer, self.width as u64)?;
+        for &val in self.values.iter() {
+            format::write_f64(writer, val)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn read_from(reader: &mut impl Read) -> Result<Self, FormatError> {
+        let height = format::read_usize(reader)?;
+        let width = format::read_usize(reader)?;
+        let len = height.checked_mul(width).ok_or_else(|| {
+            FormatError::Invalid(format!("{}x{} matrix is too large", height, width))
+        })?;
+
+        // Not preallocated, so a corrupt size runs out of file rather than memory.
+        let mut values = Vec::new();
+        for _ in 0..len {
+            values.push(format::read_f64(reader)?);
+        }
+
+        Ok(Self {
+            width,
+            height,
+            values,
+        })
+    }
 }
 
 impl<T> Matrix<T>
@@ -121,24 +345,14 @@ where
     type Output = Self;
 
     fn add(self, rhs: Self) -> Self::Output {
-        if self.width != rhs.width {
-            panic!(
-                "lhs width is {} and rhs width is {}, which is an invalid combination",
-                self.width, rhs.width
-            );
-        }
-
-        if self.height != rhs.height {
-            panic!(
-                "lhs height is {} and rhs height is {}, which is an invalid combination",
-                self.height, rhs.height
-            );
+        if let Err(err) = self.check_same_shape(&rhs, "add") {
+            panic!("{}", err);
         }
 
         let values = self
             .values
             .into_iter()
-            .zip(rhs.values.into_iter())
+            .zip(rhs.values)
             .map(|(lhs_value, rhs_value): (T, T)| lhs_value + rhs_value)
             .collect::<Vec<T>>();
 
@@ -157,18 +371,8 @@ where
     type Output = Matrix<T>;
 
     fn add(self, rhs: Self) -> Self::Output {
-        if self.width != rhs.width {
-            panic!(
-                "lhs width is {} and rhs width is {}, which is an invalid combination",
-                self.width, rhs.width
-            );
-        }
-
-        if self.height != rhs.height {
-            panic!(
-                "lhs height is {} and rhs height is {}, which is an invalid combination",
-                self.height, rhs.height
-            );
+        if let Err(err) = self.check_same_shape(rhs, "add") {
+            panic!("{}", err);
         }
 
         Matrix::new_map(self.height, self.width, |row: usize, col: usize| {
@@ -182,23 +386,13 @@ where
     T: AddAssign<T>,
 {
     fn add_assign(&mut self, rhs: Self) {
-        if self.width != rhs.width {
-            panic!(
-                "lhs width is {} and rhs width is {}, which is an invalid combination",
-                self.width, rhs.width
-            );
-        }
-
-        if self.height != rhs.height {
-            panic!(
-                "lhs height is {} and rhs height is {}, which is an invalid combination",
-                self.height, rhs.height
-            );
+        if let Err(err) = self.check_same_shape(&rhs, "add") {
+            panic!("{}", err);
         }
 
         self.values
             .iter_mut()
-            .zip(rhs.values.into_iter())
+            .zip(rhs.values)
             .for_each(|(lhs_mut_ref, rhs_value): (&mut T, T)| *lhs_mut_ref += rhs_value);
     }
 }
@@ -208,18 +402,8 @@ where
     T: AddAssign<T> + Copy,
 {
     fn add_assign(&mut self, rhs: &Matrix<T>) {
-        if self.width != rhs.width {
-            panic!(
-                "lhs width is {} and rhs width is {}, which is an invalid combination",
-                self.width, rhs.width
-            );
-        }
-
-        if self.height != rhs.height {
-            panic!(
-                "lhs height is {} and rhs height is {}, which is an invalid combination",
-                self.height, rhs.height
-            );
+        if let Err(err) = self.check_same_shape(rhs, "add") {
+            panic!("{}", err);
         }
 
         self.values
@@ -236,24 +420,14 @@ where
     type Output = Self;
 
     fn sub(self, rhs: Self) -> Self::Output {
-        if self.width != rhs.width {
-            panic!(
-                "lhs width is {} and rhs width is {}, which is an invalid combination",
-                self.width, rhs.width
-            );
-        }
-
-        if self.height != rhs.height {
-            panic!(
-                "lhs height is {} and rhs height is {}, which is an invalid combination",
-                self.height, rhs.height
-            );
+        if let Err(err) = self.check_same_shape(&rhs, "subtract") {
+            panic!("{}", err);
         }
 
         let values = self
             .values
             .into_iter()
-            .zip(rhs.values.into_iter())
+            .zip(rhs.values)
             .map(|(lhs_value, rhs_value): (T, T)| lhs_value - rhs_value)
             .collect::<Vec<T>>();
 
@@ -272,18 +446,8 @@ where
     type Output = Matrix<T>;
 
     fn sub(self, rhs: Self) -> Self::Output {
-        if self.width != rhs.width {
-            panic!(
-                "lhs width is {} and rhs width is {}, which is an invalid combination",
-                self.width, rhs.width
-            );
-        }
-
-        if self.height != rhs.height {
-            panic!(
-                "lhs height is {} and rhs height is {}, which is an invalid combination",
-                self.height, rhs.height
-            );
+        if let Err(err) = self.check_same_shape(rhs, "subtract") {
+            panic!("{}", err);
         }
 
         Matrix::new_map(self.height, self.width, |row: usize, col: usize| {
@@ -297,23 +461,13 @@ where
     T: SubAssign<T>,
 {
     fn sub_assign(&mut self, rhs: Self) {
-        if self.width != rhs.width {
-            panic!(
-                "lhs width is {} and rhs width is {}, which is an invalid combination",
-                self.width, rhs.width
-            );
-        }
-
-        if self.height != rhs.height {
-            panic!(
-                "lhs height is {} and rhs height is {}, which is an invalid combination",
-                self.height, rhs.height
-            );
+        if let Err(err) = self.check_same_shape(&rhs, "subtract") {
+            panic!("{}", err);
         }
 
         self.values
             .iter_mut()
-            .zip(rhs.values.into_iter())
+            .zip(rhs.values)
             .for_each(|(lhs_mut_ref, rhs_value): (&mut T, T)| *lhs_mut_ref -= rhs_value);
     }
 }
@@ -323,18 +477,8 @@ where
     T: SubAssign<T> + Copy,
 {
     fn sub_assign(&mut self, rhs: &Matrix<T>) {
-        if self.width != rhs.width {
-            panic!(
-                "lhs width is {} and rhs width is {}, which is an invalid combination",
-                self.width, rhs.width
-            );
-        }
-
-        if self.height != rhs.height {
-            panic!(
-                "lhs height is {} and rhs height is {}, which is an invalid combination",
-                self.height, rhs.height
-            );
+        if let Err(err) = self.check_same_shape(rhs, "subtract") {
+            panic!("{}", err);
         }
 
         self.values
@@ -346,47 +490,44 @@ where
 
 impl<T> Mul<Matrix<T>> for Matrix<T>
 where
-    T: Mul<T, Output = T> + Add<T, Output = T> + Copy,
+    T: Mul<T, Output = T> + Add<T, Output = T> + Copy + Default,
 {
     type Output = Self;
 
     fn mul(self, rhs: Self) -> Self::Output {
-        if self.width != rhs.height {
-            panic!(
-                "lhs width is {} and rhs height is {}, which is an invalid combination",
-                self.width, rhs.height
-            );
-        }
-
-        Matrix::new_map(self.height, rhs.width, |row, col| {
-            let mut values_iter = (0..self.width).map(|i| self[row][i] * rhs[i][col]);
-
-            let first_item = values_iter.next().unwrap();
-            values_iter.fold(first_item, |sum: T, val: T| sum + val)
-        })
+        &self * &rhs
     }
 }
 
 impl<'a, T> Mul<&'a Matrix<T>> for &'a Matrix<T>
 where
-    T: Mul<T, Output = T> + Add<T, Output = T> + Copy,
+    T: Mul<T, Output = T> + Add<T, Output = T> + Copy + Default,
 {
     type Output = Matrix<T>;
 
     fn mul(self, rhs: Self) -> Self::Output {
-        if self.width != rhs.height {
-            panic!(
-                "lhs width is {} and rhs height is {}, which is an invalid combination",
-                self.width, rhs.height
-            );
+        if let Err(err) = self.check_can_multiply(rhs) {
+            panic!("{}", err);
         }
 
-        Matrix::new_map(self.height, rhs.width, |row, col| {
-            let mut values_iter = (0..self.width).map(|i| self[row][i] * rhs[i][col]);
+        // With nothing to sum over, every value of the product is zero.
+        if self.width == 0 {
+            return Matrix::new(self.height, rhs.width);
+        }
 
-            let first_item = values_iter.next().unwrap();
-            values_iter.fold(first_item, |sum: T, val: T| sum + val)
-        })
+        let mut values = Vec::with_capacity(self.height * rhs.width);
+        for row in 0..self.height {
+            values.extend(rhs[0].iter().map(|&rhs_value| self[row][0] * rhs_value));
+        }
+
+        let mut product = Matrix {
+            height: self.height,
+            width: rhs.width,
+            values,
+        };
+        self.add_remaining_terms(rhs, &mut product);
+
+        product
     }
 }
 
@@ -455,3 +596,174 @@ where
             .for_each(|lhs_mut_ref: &mut T| *lhs_mut_ref /= rhs.clone());
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::rng::{self, SimRng};
+
+    use rand::Rng;
+
+    const TRIALS: usize = 500;
+
+    fn random_matrix(rng: &mut SimRng) -> Matrix<i64> {
+        let height = rng.gen_range(0..4);
+        let width = rng.gen_range(0..4);
+
+        Matrix::new_map(height, width, |_, _| rng.gen_range(-9..=9))
+    }
+
+    // A pair of matrices, half of them the same shape so both outcomes get
+    // plenty of coverage.
+    fn random_pair(rng: &mut SimRng) -> (Matrix<i64>, Matrix<i64>) {
+        let lhs = random_matrix(rng);
+        let rhs = if rng.gen_bool(0.5) {
+            Matrix::new_map(lhs.height, lhs.width, |_, _| rng.gen_range(-9..=9))
+        } else {
+            random_matrix(rng)
+        };
+
+        (lhs, rhs)
+    }
+
+    fn values(matrix: &Matrix<i64>) -> Vec<Vec<i64>> {
+        (0..matrix.height).map(|row| matrix[row].to_vec()).collect()
+    }
+
+    fn check_elementwise(
+        operation: &'static str,
+        result: Result<Matrix<i64>, ShapeError>,
+        lhs: &Matrix<i64>,
+        rhs: &Matrix<i64>,
+        func: impl Fn(i64, i64) -> i64,
+    ) {
+        if lhs.get_shape() == rhs.get_shape() {
+            let expected = Matrix::new_map(lhs.height, lhs.width, |row, col| {
+                func(lhs[row][col], rhs[row][col])
+            });
+            let result = result.unwrap();
+            assert_eq!(result.get_shape(), lhs.get_shape());
+            assert_eq!(values(&result), values(&expected));
+        } else {
+            assert_eq!(
+                result.unwrap_err(),
+                ShapeError {
+                    operation,
+                    lhs: lhs.get_shape(),
+                    rhs: rhs.get_shape(),
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn checked_elementwise_operations_match_shapes() {
+        let mut rng = rng::from_seed(1);
+
+        for _ in 0..TRIALS {
+            let (lhs, rhs) = random_pair(&mut rng);
+
+            check_elementwise("add", lhs.checked_add(&rhs), &lhs, &rhs, |a, b| a + b);
+            check_elementwise("subtract", lhs.checked_sub(&rhs), &lhs, &rhs, |a, b| a - b);
+
+            let mut sum = lhs.clone();
+            let result = sum.checked_add_assign(&rhs).map(|()| sum);
+            check_elementwise("add", result, &lhs, &rhs, |a, b| a + b);
+
+            let mut difference = lhs.clone();
+            let result = difference.checked_sub_assign(&rhs).map(|()| difference);
+            check_elementwise("subtract", result, &lhs, &rhs, |a, b| a - b);
+        }
+    }
+
+    #[test]
+    fn failed_assignments_leave_the_matrix_unchanged() {
+        let mut rng = rng::from_seed(2);
+
+        for _ in 0..TRIALS {
+            let (lhs, rhs) = random_pair(&mut rng);
+            if lhs.get_shape() == rhs.get_shape() {
+                continue;
+            }
+
+            let mut matrix = lhs.clone();
+            assert!(matrix.checked_add_assign(&rhs).is_err());
+            assert!(matrix.checked_sub_assign(&rhs).is_err());
+            assert_eq!(matrix.get_shape(), lhs.get_shape());
+            assert_eq!(values(&matrix), values(&lhs));
+        }
+    }
+
+    #[test]
+    fn same_width_but_different_height_is_a_mismatch() {
+        let lhs = Matrix::<i64>::new(2, 3);
+        let rhs = Matrix::<i64>::new(4, 3);
+        let err = |operation| ShapeError {
+            operation,
+            lhs: (2, 3),
+            rhs: (4, 3),
+        };
+
+        assert_eq!(lhs.checked_add(&rhs).unwrap_err(), err("add"));
+        assert_eq!(lhs.checked_sub(&rhs).unwrap_err(), err("subtract"));
+        assert_eq!(lhs.clone().checked_add_assign(&rhs), Err(err("add")));
+        assert_eq!(lhs.clone().checked_sub_assign(&rhs), Err(err("subtract")));
+    }
+
+    #[test]
+    fn checked_mul_matches_a_naive_product() {
+        let mut rng = rng::from_seed(3);
+
+        for _ in 0..TRIALS {
+            let lhs = random_matrix(&mut rng);
+            // Half the time, high enough to multiply.
+            let rhs = if rng.gen_bool(0.5) {
+                let width = rng.gen_range(0..4);
+                Matrix::new_map(lhs.width, width, |_, _| rng.gen_range(-9..=9))
+            } else {
+                random_matrix(&mut rng)
+            };
+
+            match lhs.checked_mul(&rhs) {
+                Ok(product) => {
+                    assert_eq!(lhs.width, rhs.height);
+                    let expected = Matrix::new_map(lhs.height, rhs.width, |row, col| {
+                        (0..lhs.width).map(|i| lhs[row][i] * rhs[i][col]).sum()
+                    });
+                    assert_eq!(product.get_shape(), (lhs.height, rhs.width));
+                    assert_eq!(values(&product), values(&expected));
+
+                    let mut out = Matrix::new_map(lhs.height, rhs.width, |_, _| 7);
+                    lhs.mul_into(&rhs, &mut out);
+                    assert_eq!(values(&out), values(&expected));
+                }
+                Err(err) => {
+                    assert_ne!(lhs.width, rhs.height);
+                    assert_eq!(
+                        err,
+                        ShapeError {
+                            operation: "multiply",
+                            lhs: lhs.get_shape(),
+                            rhs: rhs.get_shape(),
+                        }
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn empty_inner_dimension_multiplies_to_zero() {
+        let lhs = Matrix::<f64>::new(2, 0);
+        let rhs = Matrix::<f64>::new(0, 3);
+
+        let product = &lhs * &rhs;
+        assert_eq!(product.get_shape(), (2, 3));
+        assert!((0..2).all(|row| product[row] == [0.0; 3]));
+
+        let mut out = Matrix::new_map(2, 3, |_, _| 1.0);
+        lhs.mul_into(&rhs, &mut out);
+        assert!((0..2).all(|row| out[row] == [0.0; 3]));
+    }
+}
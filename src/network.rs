@@ -1,26 +1,227 @@
+use crate::activation::Activation;
+use crate::format::{self, FormatError};
+use crate::gradient::{Gradients, Loss};
 use crate::matrix::Matrix;
+use crate::rng::SimRng;
 
-use rand::{thread_rng, Rng};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::str::FromStr;
+
+use rand::Rng;
 use rand_distr::StandardNormal;
 
-use sdl2::pixels::Color;
-use sdl2::rect::{Point, Rect};
-use sdl2::render::Canvas;
-use sdl2::video::Window;
+use crate::render::{Color, Renderer};
+
+/// Scratch space for `Network::evaluate_into` and
+/// `Network::evaluate_batch_into`, holding the values of every layer. It fits
+/// itself to whichever network and batch size uses it, but only allocates
+/// when either changes from the last evaluation.
+#[derive(Clone, Debug, Default)]
+pub struct Workspace {
+    layers: Vec<Matrix<f64>>,
+}
+
+impl Workspace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The values of layer `layer` as of the last evaluation, where layer 0
+    /// is the input. For a batch, these are the values for its first row.
+    pub fn get_layer(&self, layer: usize) -> &[f64] {
+        &self.layers[layer][0]
+    }
+
+    fn fit(&mut self, rows: usize, shape: &[usize]) {
+        let fits = self.layers.len() == shape.len()
+            && self
+                .layers
+                .iter()
+                .zip(shape.iter())
+                .all(|(layer, &layer_size)| layer.get_shape() == (rows, layer_size));
+
+        if !fits {
+            self.layers = shape
+                .iter()
+                .map(|&layer_size| Matrix::new(rows, layer_size))
+                .collect();
+        }
+    }
+}
+
+/// Ways of breeding a child network from two parents of the same shape.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Crossover {
+    /// Each weight and bias comes from either parent with equal chance.
+    #[default]
+    Uniform,
+    /// Each neuron's incoming weights and bias come from the same parent.
+    Neuron,
+    /// Genes before a random cut in the flattened genome come from the first
+    /// parent, and the rest from the second.
+    SinglePoint,
+    /// Genes between two random cuts in the flattened genome come from the
+    /// second parent, and the rest from the first.
+    TwoPoint,
+    /// BLX-α: each gene is drawn uniformly from the range between the
+    /// parents' genes, widened on both sides by `alpha` times its length.
+    Blend { alpha: f64 },
+    /// SBX: each gene is spread around the parents' genes, mimicking
+    /// single-point crossover on binary genes. Larger `eta` keeps children
+    /// closer to their parents.
+    SimulatedBinary { eta: f64 },
+}
+
+impl Crossover {
+    /// Checks the operator's parameters: blend `alpha` and SBX `eta` must be
+    /// finite and not negative.
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            Crossover::Blend { alpha } if !(alpha.is_finite() && alpha >= 0.0) => Err(format!(
+                "Blend alpha must be finite and not negative, got {}",
+                alpha
+            )),
+            Crossover::SimulatedBinary { eta } if !(eta.is_finite() && eta >= 0.0) => Err(format!(
+                "Simulated binary crossover eta must be finite and not negative, got {}",
+                eta
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        match *self {
+            Crossover::Uniform => format::write_u8(writer, 0),
+            Crossover::Neuron => format::write_u8(writer, 1),
+            Crossover::SinglePoint => format::write_u8(writer, 2),
+            Crossover::TwoPoint => format::write_u8(writer, 3),
+            Crossover::Blend { alpha } => {
+                format::write_u8(writer, 4)?;
+                format::write_f64(writer, alpha)
+            }
+            Crossover::SimulatedBinary { eta } => {
+                format::write_u8(writer, 5)?;
+                format::write_f64(writer, eta)
+            }
+        }
+    }
+
+    /// Reads an operator written by `write_to`, checking its parameters, see
+    /// `validate`.
+    pub(crate) fn read_from(reader: &mut impl Read) -> Result<Self, FormatError> {
+        let operator = match format::read_u8(reader)? {
+            0 => Crossover::Uniform,
+            1 => Crossover::Neuron,
+            2 => Crossover::SinglePoint,
+            3 => Crossover::TwoPoint,
+            4 => Crossover::Blend {
+                alpha: format::read_f64(reader)?,
+            },
+            5 => Crossover::SimulatedBinary {
+                eta: format::read_f64(reader)?,
+            },
+            other => {
+                return Err(FormatError::Invalid(format!(
+                    "{} is not a valid crossover operator",
+                    other
+                )))
+            }
+        };
+        operator.validate().map_err(FormatError::Invalid)?;
+
+        Ok(operator)
+    }
+}
+
+/// Written the same way it's parsed, e.g. `two-point` or `blend:0.5`.
+impl fmt::Display for Crossover {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Crossover::Uniform => write!(f, "uniform"),
+            Crossover::Neuron => write!(f, "neuron"),
+            Crossover::SinglePoint => write!(f, "single-point"),
+            Crossover::TwoPoint => write!(f, "two-point"),
+            Crossover::Blend { alpha } => write!(f, "blend:{}", alpha),
+            Crossover::SimulatedBinary { eta } => write!(f, "sbx:{}", eta),
+        }
+    }
+}
+
+impl FromStr for Crossover {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, param) = match s.split_once(':') {
+            Some((name, param)) => (name, Some(param)),
+            None => (s, None),
+        };
+
+        let parse_param = |param: &str, what: &str| {
+            param
+                .parse::<f64>()
+                .ok()
+                .filter(|val| val.is_finite() && *val >= 0.0)
+                .ok_or_else(|| format!("{} must not be negative, got '{}'", what, param))
+        };
+
+        match (name, param) {
+            ("uniform", None) => Ok(Crossover::Uniform),
+            ("neuron", None) => Ok(Crossover::Neuron),
+            ("single-point", None) => Ok(Crossover::SinglePoint),
+            ("two-point", None) => Ok(Crossover::TwoPoint),
+            ("blend", None) => Ok(Crossover::Blend { alpha: 0.5 }),
+            ("blend", Some(alpha)) => Ok(Crossover::Blend {
+                alpha: parse_param(alpha, "Blend alpha")?,
+            }),
+            ("sbx", None) => Ok(Crossover::SimulatedBinary { eta: 2.0 }),
+            ("sbx", Some(eta)) => Ok(Crossover::SimulatedBinary {
+                eta: parse_param(eta, "Simulated binary crossover eta")?,
+            }),
+            _ => Err(format!("Unknown crossover operator '{}'", s)),
+        }
+    }
+}
 
 #[derive(Clone)]
 pub struct Network {
     weights: Vec<Matrix<f64>>,
     biases: Vec<Matrix<f64>>,
     shape: Vec<usize>,
+    // One per layer after the input.
+    activations: Vec<Activation>,
 }
 
 impl Network {
-    const NODE_COLOR: Color = Color::RGB(255, 0, 0);
-    const CONNECTION_COLOR: Color = Color::RGB(0, 255, 0);
+    const NODE_COLOR: Color = Color::rgb(255, 0, 0);
+    const ACTIVE_NODE_COLOR: Color = Color::rgb(255, 255, 255);
+    const POSITIVE_COLOR: Color = Color::rgb(0, 255, 0);
+    const NEGATIVE_COLOR: Color = Color::rgb(255, 0, 0);
+
+    // Both in multiples of the node size.
+    const NODE_SPACING: u32 = 5;
+    const MARGIN: u32 = 4;
 
-    pub fn new(shape: Vec<usize>) -> Self {
-        let mut rng = thread_rng();
+    const MAGIC: &'static [u8; 4] = b"SNET";
+    const FORMAT_VERSION: u32 = 1;
+
+    /// A network with random weights and biases, where every layer uses
+    /// sigmoid activation.
+    pub fn new(shape: Vec<usize>, rng: &mut SimRng) -> Self {
+        let activations = vec![Activation::Sigmoid; shape.len().saturating_sub(1)];
+        Self::with_activations(shape, activations, rng)
+    }
+
+    /// Same as `new`, but with the activation of each layer after the input
+    /// given by `activations`. Only the output layer may use softmax.
+    pub fn with_activations(
+        shape: Vec<usize>,
+        activations: Vec<Activation>,
+        rng: &mut SimRng,
+    ) -> Self {
+        Self::check_activations(&shape, &activations).unwrap_or_else(|err| panic!("{}", err));
 
         let weights = shape
             .iter()
@@ -45,73 +246,534 @@ impl Network {
             weights,
             biases,
             shape,
+            activations,
         }
     }
 
     pub fn evaluate(&self, values: Vec<f64>) -> Vec<f64> {
-        let input: Matrix<f64> =
-            Matrix::new_map(1, values.len(), |_row: usize, col: usize| values[col]);
+        self.evaluate_into(&values, &mut Workspace::new()).to_vec()
+    }
 
-        let result_matrix =
-            self.weights
-                .iter()
-                .zip(self.biases.iter())
-                .fold(input, |total, (weight, bias)| {
-                    let multiplied = &total * weight;
-                    let biased = &multiplied + bias;
-                    biased.map(|val: f64| Network::sigmoid(val))
-                });
+    /// Same as `evaluate`, but works in `workspace` rather than allocating,
+    /// and returns the output from there. Reusing a workspace across calls,
+    /// e.g. one per thread or per game, means nothing is allocated after the
+    /// first call.
+    pub fn evaluate_into<'a>(&self, input: &[f64], workspace: &'a mut Workspace) -> &'a [f64] {
+        if input.len() != self.shape[0] {
+            panic!(
+                "Network takes {} inputs, but was given {}",
+                self.shape[0],
+                input.len()
+            );
+        }
+
+        workspace.fit(1, &self.shape);
+        workspace.layers[0][0].copy_from_slice(input);
+        self.forward(workspace);
+
+        &workspace.layers.last().unwrap()[0]
+    }
+
+    /// Evaluates every row of `inputs` as a separate input, returning a
+    /// matrix with the matching row of output for each. This takes one
+    /// matrix multiply per layer however many rows there are, so it's the
+    /// quicker way to evaluate many inputs at once, e.g. to step many games
+    /// in lock-step.
+    pub fn evaluate_batch(&self, inputs: &Matrix<f64>) -> Matrix<f64> {
+        let mut workspace = Workspace::new();
+        self.evaluate_batch_into(inputs, &mut workspace);
+
+        workspace.layers.pop().unwrap()
+    }
+
+    /// Same as `evaluate_batch`, but works in `workspace` rather than
+    /// allocating, as `evaluate_into` does.
+    pub fn evaluate_batch_into<'a>(
+        &self,
+        inputs: &Matrix<f64>,
+        workspace: &'a mut Workspace,
+    ) -> &'a Matrix<f64> {
+        if inputs.get_width() != self.shape[0] {
+            panic!(
+                "Network takes {} inputs, but was given a batch of {}",
+                self.shape[0],
+                inputs.get_width()
+            );
+        }
+
+        let rows = inputs.get_height();
+        workspace.fit(rows, &self.shape);
+        for row in 0..rows {
+            workspace.layers[0][row].copy_from_slice(&inputs[row]);
+        }
+        self.forward(workspace);
+
+        workspace.layers.last().unwrap()
+    }
+
+    // Fills in every layer of `workspace` after the first, which holds the
+    // input, one row per input.
+    fn forward(&self, workspace: &mut Workspace) {
+        for (layer, (weight, bias)) in self.weights.iter().zip(self.biases.iter()).enumerate() {
+            let (previous, rest) = workspace.layers[layer..].split_first_mut().unwrap();
+            let current = &mut rest[0];
+
+            previous.mul_into(weight, current);
+            for row in 0..current.get_height() {
+                for (val, &bias) in current[row].iter_mut().zip(bias[0].iter()) {
+                    *val += bias;
+                }
+                self.activations[layer].apply(&mut current[row]);
+            }
+        }
+    }
+
+    /// Same as `evaluate`, but returns the values of every layer, starting
+    /// with `values` itself and ending with the output.
+    pub fn evaluate_layers(&self, values: Vec<f64>) -> Vec<Vec<f64>> {
+        let mut workspace = Workspace::new();
+        self.evaluate_into(&values, &mut workspace);
+
+        workspace
+            .layers
+            .iter()
+            .map(|layer| layer[0].to_vec())
+            .collect()
+    }
+
+    /// Backpropagates `loss` over a batch, where each row of `inputs` is
+    /// one example and the same row of `targets` the outputs wanted for it.
+    /// Returns the loss of the batch as it stands, along with its gradient
+    /// with respect to every weight and bias. The batch mustn't be empty,
+    /// since there's no loss to average over.
+    pub fn gradients(
+        &self,
+        inputs: &Matrix<f64>,
+        targets: &Matrix<f64>,
+        loss: Loss,
+    ) -> (f64, Gradients) {
+        if inputs.get_height() == 0 {
+            panic!("Cannot compute gradients of an empty batch");
+        }
+
+        if inputs.get_width() != self.shape[0] {
+            panic!(
+                "Network takes {} inputs, but was given a batch of {}",
+                self.shape[0],
+                inputs.get_width()
+            );
+        }
+
+        let outputs = *self.shape.last().unwrap();
+        if targets.get_shape() != (inputs.get_height(), outputs) {
+            panic!(
+                "A batch of {} inputs needs {}x{} targets, but was given {}x{}",
+                inputs.get_height(),
+                inputs.get_height(),
+                outputs,
+                targets.get_height(),
+                targets.get_width()
+            );
+        }
+
+        // Run forwards, keeping each layer's weighted sums as well as its
+        // values, since some activations' derivatives need them.
+        let mut sums = Vec::with_capacity(self.weights.len());
+        let mut values = vec![inputs.clone()];
+        for (layer, (weight, bias)) in self.weights.iter().zip(self.biases.iter()).enumerate() {
+            let mut sum = values.last().unwrap() * weight;
+            for row in 0..sum.get_height() {
+                for (val, &bias) in sum[row].iter_mut().zip(bias[0].iter()) {
+                    *val += bias;
+                }
+            }
+
+            let mut value = sum.clone();
+            for row in 0..value.get_height() {
+                self.activations[layer].apply(&mut value[row]);
+            }
+
+            sums.push(sum);
+            values.push(value);
+        }
 
-        (0..result_matrix.get_width())
-            .map(|i| result_matrix[0][i])
-            .collect::<Vec<f64>>()
+        let batch_loss = loss.loss(values.last().unwrap(), targets);
+
+        // Then backwards, carrying the gradient with respect to each layer's
+        // values down to the one before.
+        let mut weight_gradients = Vec::with_capacity(self.weights.len());
+        let mut bias_gradients = Vec::with_capacity(self.biases.len());
+        let mut gradient = loss.gradient(values.last().unwrap(), targets);
+        for layer in (0..self.weights.len()).rev() {
+            for row in 0..gradient.get_height() {
+                self.activations[layer].backprop(
+                    &sums[layer][row],
+                    &values[layer + 1][row],
+                    &mut gradient[row],
+                );
+            }
+
+            weight_gradients.push(&values[layer].transpose() * &gradient);
+            bias_gradients.push(Matrix::new_map(1, gradient.get_width(), |_, col| {
+                (0..gradient.get_height())
+                    .map(|row| gradient[row][col])
+                    .sum()
+            }));
+
+            if layer > 0 {
+                gradient = &gradient * &self.weights[layer].transpose();
+            }
+        }
+
+        weight_gradients.reverse();
+        bias_gradients.reverse();
+
+        (batch_loss, Gradients::new(weight_gradients, bias_gradients))
+    }
+
+    // Every weight matrix and then every bias matrix, for an optimiser to
+    // step.
+    pub(crate) fn parameters_mut(&mut self) -> impl Iterator<Item = &mut Matrix<f64>> {
+        self.weights.iter_mut().chain(self.biases.iter_mut())
     }
 
     pub fn get_shape(&self) -> &[usize] {
         &self.shape
     }
 
-    pub fn merge(&self, other: &Self) -> Self {
-        let mut rng = thread_rng();
-        let new_weights = self
-            .weights
+    /// The activation of each layer after the input.
+    pub fn get_activations(&self) -> &[Activation] {
+        &self.activations
+    }
+
+    // Checks there's one activation per layer after the input, and that only
+    // the output layer uses softmax.
+    fn check_activations(shape: &[usize], activations: &[Activation]) -> Result<(), String> {
+        if activations.len() + 1 != shape.len() {
+            return Err(format!(
+                "Network of shape {:?} needs {} activations, but was given {}",
+                shape,
+                shape.len().saturating_sub(1),
+                activations.len()
+            ));
+        }
+
+        let hidden = &activations[..activations.len().saturating_sub(1)];
+        if let Some(layer) = hidden.iter().position(|&act| act == Activation::Softmax) {
+            return Err(format!(
+                "Only the output layer can use softmax, but layer {} does",
+                layer + 1
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Checks that the network takes `inputs` values and produces `outputs`.
+    pub fn check_io(&self, inputs: usize, outputs: usize) -> Result<(), FormatError> {
+        if self.shape.first() != Some(&inputs) || self.shape.last() != Some(&outputs) {
+            return Err(FormatError::WrongShape {
+                expected_inputs: inputs,
+                expected_outputs: outputs,
+                found: self.shape.clone(),
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), FormatError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, FormatError> {
+        Self::read_from(&mut BufReader::new(File::open(path)?))
+    }
+
+    /// Writes the network in the following format, all little-endian:
+    ///
+    /// ```text
+    /// magic        b"SNET"
+    /// version      u32, currently 1
+    /// layer count  u32
+    /// shape        u64 per layer
+    /// activations  one per layer after the first
+    /// weights      one matrix per pair of adjacent layers
+    /// biases       one matrix per layer after the first
+    /// ```
+    ///
+    /// Each activation is a `u8` tag, followed by an `f64` parameter for
+    /// leaky ReLU and ELU. Each matrix is written as its height and width
+    /// (`u64`s) and then its values (`f64`s) row by row, see
+    /// `Matrix::write_to`.
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        format::write_header(writer, Self::MAGIC, Self::FORMAT_VERSION)?;
+        format::write_u32(writer, self.shape.len() as u32)?;
+        for &layer_size in self.shape.iter() {
+            format::write_u64(writer, layer_size as u64)?;
+        }
+        for activation in self.activations.iter() {
+            activation.write_to(writer)?;
+        }
+
+        for matrix in self.weights.iter().chain(self.biases.iter()) {
+            matrix.write_to(writer)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads a network written by `write_to`, checking that every matrix
+    /// matches the stored shape.
+    pub fn read_from(reader: &mut impl Read) -> Result<Self, FormatError> {
+        format::read_header(reader, Self::MAGIC, Self::FORMAT_VERSION)?;
+
+        let layer_count = format::read_u32(reader)?;
+        if layer_count < 2 {
+            return Err(FormatError::Invalid(format!(
+                "network has {} layers, but needs at least an input and an output",
+                layer_count
+            )));
+        }
+
+        let shape = (0..layer_count)
+            .map(|_| format::read_usize(reader))
+            .collect::<Result<Vec<usize>, FormatError>>()?;
+
+        let activations = (1..layer_count)
+            .map(|_| Activation::read_from(reader))
+            .collect::<Result<Vec<Activation>, FormatError>>()?;
+        Self::check_activations(&shape, &activations).map_err(FormatError::Invalid)?;
+
+        let mut read_matrix = |height: usize, width: usize, name: &str, layer: usize| {
+            let matrix = Matrix::read_from(reader)?;
+            if matrix.get_height() != height || matrix.get_width() != width {
+                return Err(FormatError::Invalid(format!(
+                    "{} of layer {} are {}x{}, but shape {:?} needs {}x{}",
+                    name,
+                    layer,
+                    matrix.get_height(),
+                    matrix.get_width(),
+                    shape,
+                    height,
+                    width
+                )));
+            }
+
+            Ok(matrix)
+        };
+
+        let weights = shape
             .iter()
-            .zip(other.weights.iter())
-            .map(|(layer_one, layer_two)| {
-                Matrix::new_map(layer_one.get_height(), layer_one.get_width(), |row, col| {
-                    if rng.gen_bool(0.5) {
-                        layer_one[row][col]
-                    } else {
-                        layer_two[row][col]
-                    }
-                })
+            .zip(shape[1..].iter())
+            .enumerate()
+            .map(|(layer, (&input_size, &output_size))| {
+                read_matrix(input_size, output_size, "weights", layer + 1)
             })
-            .collect();
+            .collect::<Result<Vec<Matrix<f64>>, FormatError>>()?;
 
-        let new_biases = self
-            .biases
+        let biases = shape[1..]
             .iter()
-            .zip(other.weights.iter())
-            .map(|(bias_one, bias_two)| {
-                Matrix::new_map(bias_one.get_height(), bias_one.get_width(), |row, col| {
-                    if rng.gen_bool(0.5) {
-                        bias_one[row][col]
+            .enumerate()
+            .map(|(layer, &layer_size)| read_matrix(1, layer_size, "biases", layer + 1))
+            .collect::<Result<Vec<Matrix<f64>>, FormatError>>()?;
+
+        Ok(Self {
+            weights,
+            biases,
+            shape,
+            activations,
+        })
+    }
+
+    /// Breeds a child from this network and `other`, which must have the
+    /// same shape and activations. The child always has them too.
+    pub fn crossover(&self, other: &Self, operator: Crossover, rng: &mut SimRng) -> Self {
+        if self.shape != other.shape {
+            panic!(
+                "Cannot cross a network of shape {:?} with one of shape {:?}",
+                self.shape, other.shape
+            );
+        }
+
+        if let Err(err) = operator.validate() {
+            panic!("{}", err);
+        }
+
+        if self.activations != other.activations {
+            panic!(
+                "Cannot cross a network with activations {:?} with one with {:?}",
+                self.activations, other.activations
+            );
+        }
+
+        match operator {
+            Crossover::Uniform => self.combine(other, |_, gene_one, gene_two| {
+                if rng.gen_bool(0.5) {
+                    gene_one
+                } else {
+                    gene_two
+                }
+            }),
+            Crossover::Neuron => self.cross_neurons(other, rng),
+            Crossover::SinglePoint => {
+                let cut = rng.gen_range(0..=self.gene_count());
+                self.combine(
+                    other,
+                    |index, gene_one, gene_two| {
+                        if index < cut {
+                            gene_one
+                        } else {
+                            gene_two
+                        }
+                    },
+                )
+            }
+            Crossover::TwoPoint => {
+                let gene_count = self.gene_count();
+                let mut cuts = [rng.gen_range(0..=gene_count), rng.gen_range(0..=gene_count)];
+                cuts.sort_unstable();
+
+                self.combine(other, |index, gene_one, gene_two| {
+                    if index >= cuts[0] && index < cuts[1] {
+                        gene_two
                     } else {
-                        bias_two[row][col]
+                        gene_one
                     }
                 })
-            })
-            .collect();
+            }
+            Crossover::Blend { alpha } => self.combine(other, |_, gene_one, gene_two| {
+                let low = f64::min(gene_one, gene_two);
+                let high = f64::max(gene_one, gene_two);
+                let spread = alpha * (high - low);
+
+                rng.gen_range((low - spread)..=(high + spread))
+            }),
+            Crossover::SimulatedBinary { eta } => {
+                self.combine(other, |_, gene_one, gene_two| {
+                    let u: f64 = rng.gen();
+                    let beta = if u <= 0.5 {
+                        f64::powf(2.0 * u, 1.0 / (eta + 1.0))
+                    } else {
+                        f64::powf(1.0 / (2.0 * (1.0 - u)), 1.0 / (eta + 1.0))
+                    };
+
+                    // SBX makes a pair of children, spread evenly around the
+                    // parents' midpoint, so take one of them at random.
+                    let (near, far) = if rng.gen_bool(0.5) {
+                        (gene_one, gene_two)
+                    } else {
+                        (gene_two, gene_one)
+                    };
+
+                    0.5 * ((1.0 + beta) * near + (1.0 - beta) * far)
+                })
+            }
+        }
+    }
+
+    /// Every weight and bias, in the order they're saved: every weight
+    /// matrix, then every bias matrix, each row by row.
+    pub fn get_genes(&self) -> Vec<f64> {
+        let mut genes = Vec::with_capacity(self.gene_count());
+        for matrix in self.weights.iter().chain(self.biases.iter()) {
+            for row in 0..matrix.get_height() {
+                genes.extend_from_slice(&matrix[row]);
+            }
+        }
+
+        genes
+    }
+
+    // Number of weights and biases, i.e. the length of the flattened genome.
+    fn gene_count(&self) -> usize {
+        self.weights
+            .iter()
+            .chain(self.biases.iter())
+            .map(|matrix| matrix.get_height() * matrix.get_width())
+            .sum()
+    }
+
+    // Builds a network of the same shape by calling `func` with the index of
+    // each gene in the flattened genome and that gene in both parents. Genes
+    // are ordered as in `get_genes`.
+    fn combine<F>(&self, other: &Self, mut func: F) -> Self
+    where
+        F: FnMut(usize, f64, f64) -> f64,
+    {
+        let mut index = 0;
+        let mut next_gene = |gene_one: f64, gene_two: f64| {
+            let gene = func(index, gene_one, gene_two);
+            index += 1;
+
+            gene
+        };
+
+        let mut combine_layers = |layers_one: &[Matrix<f64>], layers_two: &[Matrix<f64>]| {
+            layers_one
+                .iter()
+                .zip(layers_two.iter())
+                .map(|(layer_one, layer_two)| {
+                    Matrix::new_map(layer_one.get_height(), layer_one.get_width(), |row, col| {
+                        next_gene(layer_one[row][col], layer_two[row][col])
+                    })
+                })
+                .collect::<Vec<Matrix<f64>>>()
+        };
+
+        let new_weights = combine_layers(&self.weights, &other.weights);
+        let new_biases = combine_layers(&self.biases, &other.biases);
 
         Self {
             weights: new_weights,
             biases: new_biases,
             shape: self.shape.clone(),
+            activations: self.activations.clone(),
         }
     }
 
-    pub fn mutate(&self, mutation_prob: f64, mutation_amount: f64) -> Self {
-        let mut rng = thread_rng();
+    // Takes each neuron's incoming weights and bias together from one parent
+    // or the other, so neurons that work well aren't split up.
+    fn cross_neurons(&self, other: &Self, rng: &mut SimRng) -> Self {
+        let mut new_weights = Vec::with_capacity(self.weights.len());
+        let mut new_biases = Vec::with_capacity(self.biases.len());
+
+        for (layer, &layer_size) in self.shape[1..].iter().enumerate() {
+            let from_self = (0..layer_size)
+                .map(|_| rng.gen_bool(0.5))
+                .collect::<Vec<bool>>();
+            let pick = |matrix_one: &Matrix<f64>, matrix_two: &Matrix<f64>| {
+                // A neuron's incoming weights are a column of the weight matrix.
+                Matrix::new_map(
+                    matrix_one.get_height(),
+                    matrix_one.get_width(),
+                    |row, col| {
+                        if from_self[col] {
+                            matrix_one[row][col]
+                        } else {
+                            matrix_two[row][col]
+                        }
+                    },
+                )
+            };
+
+            new_weights.push(pick(&self.weights[layer], &other.weights[layer]));
+            new_biases.push(pick(&self.biases[layer], &other.biases[layer]));
+        }
+
+        Self {
+            weights: new_weights,
+            biases: new_biases,
+            shape: self.shape.clone(),
+            activations: self.activations.clone(),
+        }
+    }
+
+    pub fn mutate(&self, mutation_prob: f64, mutation_amount: f64, rng: &mut SimRng) -> Self {
         let new_weights = self
             .weights
             .iter()
@@ -146,57 +808,620 @@ impl Network {
             weights: new_weights,
             biases: new_biases,
             shape: self.shape.clone(),
+            activations: self.activations.clone(),
         }
     }
 
-    pub fn render(&self, canvas: &mut Canvas<Window>, scale: u16) {
-        let x_offset = i32::from(4 * scale);
-        let y_offset = i32::from(4 * scale);
-        let space_between = 5;
+    /// Size in pixels of the view drawn by `render` at `scale`.
+    pub fn render_size(&self, scale: u16) -> (u32, u32) {
+        let scale = u32::from(scale);
+        let layers = self.shape.len() as u32;
+        let tallest_layer = *self.shape.iter().max().unwrap() as u32;
 
-        for (start_layer, (&start_layer_size, &end_layer_size)) in
-            self.shape.iter().zip(self.shape[1..].iter()).enumerate()
-        {
-            for start_node in 0..start_layer_size {
-                let start_point = Point::new(
-                    ((start_layer as i32) * i32::from(scale) * space_between)
-                        + i32::from(scale / 2)
-                        + x_offset,
-                    ((start_node as i32) * i32::from(scale) * space_between)
-                        + i32::from(scale / 2)
-                        + y_offset,
-                );
-                for end_node in 0..end_layer_size {
-                    let end_point = Point::new(
-                        (((start_layer + 1) as i32) * i32::from(scale) * space_between)
-                            + i32::from(scale / 2)
-                            + x_offset,
-                        ((end_node as i32) * i32::from(scale) * space_between)
-                            + i32::from(scale / 2)
-                            + y_offset,
+        (
+            2 * Self::MARGIN * scale + (layers - 1) * Self::NODE_SPACING * scale + scale,
+            2 * Self::MARGIN * scale + (tallest_layer - 1) * Self::NODE_SPACING * scale + scale,
+        )
+    }
+
+    /// Draws the network with its top left corner at (`x`, `y`), with each
+    /// node a square `scale` pixels across. Connections are green for
+    /// positive weights and red for negative ones, brighter and thicker the
+    /// bigger the weight is compared to the rest of its layer. Given the
+    /// workspace of an evaluation, each node is shaded by how strongly it
+    /// fired, see `node_strengths`.
+    pub fn render(
+        &self,
+        renderer: &mut impl Renderer,
+        x: i32,
+        y: i32,
+        scale: u16,
+        activations: Option<&Workspace>,
+    ) {
+        let tallest_layer = *self.shape.iter().max().unwrap();
+        let node_position = |layer: usize, node: usize| {
+            // Shorter layers are centred against the tallest.
+            let layer_offset = (tallest_layer - self.shape[layer]) * Self::NODE_SPACING as usize;
+            (
+                x + i32::from(scale) * (Self::MARGIN + layer as u32 * Self::NODE_SPACING) as i32,
+                y + i32::from(scale)
+                    * (Self::MARGIN as i32
+                        + (layer_offset / 2 + node * Self::NODE_SPACING as usize) as i32),
+            )
+        };
+        let node_centre = |layer: usize, node: usize| {
+            let (node_x, node_y) = node_position(layer, node);
+            (node_x + i32::from(scale / 2), node_y + i32::from(scale / 2))
+        };
+
+        for (layer, weights) in self.weights.iter().enumerate() {
+            let largest_weight = (0..weights.get_height())
+                .flat_map(|row| weights[row].iter())
+                .fold(0.0, |largest: f64, weight| largest.max(weight.abs()));
+
+            // Weakest first, so the strongest connections end up on top.
+            let mut connections = (0..weights.get_height())
+                .flat_map(|start| (0..weights.get_width()).map(move |end| (start, end)))
+                .collect::<Vec<(usize, usize)>>();
+            connections.sort_by(|&(start1, end1), &(start2, end2)| {
+                weights[start1][end1]
+                    .abs()
+                    .partial_cmp(&weights[start2][end2].abs())
+                    .unwrap()
+            });
+
+            for (start_node, end_node) in connections {
+                let weight = weights[start_node][end_node];
+                let strength = if largest_weight > 0.0 {
+                    weight.abs() / largest_weight
+                } else {
+                    0.0
+                };
+                let color = if weight >= 0.0 {
+                    Self::POSITIVE_COLOR
+                } else {
+                    Self::NEGATIVE_COLOR
+                };
+
+                let start_point = node_centre(layer, start_node);
+                let end_point = node_centre(layer + 1, end_node);
+                let thickness = 1 + (strength * 2.0).round() as i32;
+                for offset in 0..thickness {
+                    renderer.draw_line(
+                        (start_point.0, start_point.1 + offset),
+                        (end_point.0, end_point.1 + offset),
+                        Self::shade(color, 0.2 + 0.8 * strength),
                     );
-                    canvas.set_draw_color(Self::CONNECTION_COLOR);
-                    canvas.draw_line(start_point, end_point).unwrap();
                 }
             }
         }
 
         for (layer, &layer_size) in self.shape.iter().enumerate() {
+            let strengths = activations
+                .map(|activations| self.node_strengths(layer, activations.get_layer(layer)));
             for node in 0..layer_size {
-                canvas.set_draw_color(Self::NODE_COLOR);
-                canvas
-                    .fill_rect(Rect::new(
-                        ((layer as i32) * i32::from(scale) * space_between) + x_offset,
-                        ((node as i32) * i32::from(scale) * space_between) + y_offset,
-                        scale.into(),
-                        scale.into(),
-                    ))
-                    .unwrap();
+                let color = match &strengths {
+                    Some(strengths) => {
+                        Self::shade(Self::ACTIVE_NODE_COLOR, 0.15 + 0.85 * strengths[node])
+                    }
+                    None => Self::NODE_COLOR,
+                };
+
+                let (node_x, node_y) = node_position(layer, node);
+                renderer.fill_rect(node_x, node_y, scale.into(), scale.into(), color);
+            }
+        }
+    }
+
+    // How strongly each node of `layer` fired, from 0 to 1, given its
+    // `values`. Layers whose activation is bounded are placed within its
+    // range, while the input layer and unbounded activations are scaled by
+    // the layer's largest absolute value.
+    fn node_strengths(&self, layer: usize, values: &[f64]) -> Vec<f64> {
+        let range = match layer {
+            0 => None,
+            _ => self.activations[layer - 1].range(),
+        };
+
+        match range {
+            Some((low, high)) => values
+                .iter()
+                .map(|&val| ((val - low) / (high - low)).clamp(0.0, 1.0))
+                .collect(),
+            None => {
+                let largest = values
+                    .iter()
+                    .fold(0.0, |largest: f64, val| largest.max(val.abs()));
+                values
+                    .iter()
+                    .map(|&val| {
+                        if largest > 0.0 {
+                            val.abs() / largest
+                        } else {
+                            0.0
+                        }
+                    })
+                    .collect()
             }
         }
     }
 
+    // `color` scaled towards black, where a brightness of 1 leaves it as is.
+    fn shade(color: Color, brightness: f64) -> Color {
+        let channel = |val: u8| (f64::from(val) * brightness).round() as u8;
+
+        Color::rgb(channel(color.r), channel(color.g), channel(color.b))
+    }
+
     pub fn sigmoid(val: f64) -> f64 {
         1.0 / (1.0 + f64::exp(-val))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::rng;
+
+    fn parents(seed: u64) -> (Network, Network) {
+        let mut rng = rng::from_seed(seed);
+        let shape = vec![3, 4, 2];
+        let activations = vec![Activation::Tanh, Activation::Softmax];
+
+        (
+            Network::with_activations(shape.clone(), activations.clone(), &mut rng),
+            Network::with_activations(shape, activations, &mut rng),
+        )
+    }
+
+    // Which parent each of the child's genes came from, 0 for the first and
+    // 1 for the second. Parent genes are drawn from a normal distribution,
+    // so they never match by chance.
+    fn origins(child: &Network, one: &Network, two: &Network) -> Vec<usize> {
+        child
+            .get_genes()
+            .iter()
+            .zip(one.get_genes().iter().zip(two.get_genes().iter()))
+            .enumerate()
+            .map(|(index, (&gene, (&gene_one, &gene_two)))| {
+                if gene == gene_one {
+                    0
+                } else if gene == gene_two {
+                    1
+                } else {
+                    panic!("Gene {} is {}, from neither parent", index, gene)
+                }
+            })
+            .collect()
+    }
+
+    // How many times the origin changes from one gene to the next.
+    fn switches(origins: &[usize]) -> usize {
+        origins.windows(2).filter(|pair| pair[0] != pair[1]).count()
+    }
+
+    fn check_shape(child: &Network, parent: &Network) {
+        assert_eq!(child.get_shape(), parent.get_shape());
+        assert_eq!(child.get_activations(), parent.get_activations());
+        for layer in 0..parent.weights.len() {
+            assert_eq!(
+                child.weights[layer].get_shape(),
+                parent.weights[layer].get_shape()
+            );
+            assert_eq!(
+                child.biases[layer].get_shape(),
+                parent.biases[layer].get_shape()
+            );
+        }
+    }
+
+    #[test]
+    fn uniform_children_take_every_gene_from_a_parent() {
+        let (one, two) = parents(1);
+        let mut rng = rng::from_seed(2);
+
+        let mut from_each = [0, 0];
+        for _ in 0..20 {
+            let child = one.crossover(&two, Crossover::Uniform, &mut rng);
+            check_shape(&child, &one);
+            for origin in origins(&child, &one, &two) {
+                from_each[origin] += 1;
+            }
+        }
+
+        assert!(from_each[0] > 0 && from_each[1] > 0);
+    }
+
+    #[test]
+    fn neuron_children_take_each_neuron_from_one_parent() {
+        let (one, two) = parents(3);
+        let mut rng = rng::from_seed(4);
+
+        for _ in 0..20 {
+            let child = one.crossover(&two, Crossover::Neuron, &mut rng);
+            check_shape(&child, &one);
+            origins(&child, &one, &two);
+
+            for layer in 0..child.weights.len() {
+                for neuron in 0..child.shape[layer + 1] {
+                    let from_one = child.biases[layer][0][neuron] == one.biases[layer][0][neuron];
+                    for input in 0..child.shape[layer] {
+                        let weight = child.weights[layer][input][neuron];
+                        let parent = if from_one { &one } else { &two };
+                        assert_eq!(weight, parent.weights[layer][input][neuron]);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn single_point_children_switch_parents_at_most_once() {
+        let (one, two) = parents(5);
+        let mut rng = rng::from_seed(6);
+
+        for _ in 0..20 {
+            let child = one.crossover(&two, Crossover::SinglePoint, &mut rng);
+            check_shape(&child, &one);
+
+            let origins = origins(&child, &one, &two);
+            assert!(switches(&origins) <= 1);
+            if switches(&origins) == 1 {
+                assert_eq!(origins[0], 0);
+            }
+        }
+    }
+
+    #[test]
+    fn two_point_children_take_one_run_from_the_second_parent() {
+        let (one, two) = parents(7);
+        let mut rng = rng::from_seed(8);
+
+        for _ in 0..20 {
+            let child = one.crossover(&two, Crossover::TwoPoint, &mut rng);
+            check_shape(&child, &one);
+
+            let origins = origins(&child, &one, &two);
+            assert!(switches(&origins) <= 2);
+            assert!(switches(&origins) < 2 || origins[0] == 0);
+        }
+    }
+
+    #[test]
+    fn blend_children_stay_within_the_widened_range() {
+        let (one, two) = parents(9);
+        let mut rng = rng::from_seed(10);
+        let alpha = 0.5;
+
+        for _ in 0..20 {
+            let child = one.crossover(&two, Crossover::Blend { alpha }, &mut rng);
+            check_shape(&child, &one);
+
+            let genes = one.get_genes().into_iter().zip(two.get_genes());
+            for (gene, (gene_one, gene_two)) in child.get_genes().into_iter().zip(genes) {
+                let spread = alpha * (gene_one - gene_two).abs();
+                assert!(gene >= gene_one.min(gene_two) - spread);
+                assert!(gene <= gene_one.max(gene_two) + spread);
+            }
+        }
+    }
+
+    #[test]
+    fn simulated_binary_children_keep_the_parents_shape() {
+        let (one, two) = parents(11);
+        let mut rng = rng::from_seed(12);
+
+        for _ in 0..20 {
+            let child = one.crossover(&two, Crossover::SimulatedBinary { eta: 2.0 }, &mut rng);
+            check_shape(&child, &one);
+            assert_eq!(child.get_genes().len(), one.get_genes().len());
+            assert!(child.get_genes().iter().all(|gene| gene.is_finite()));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot cross a network with activations")]
+    fn crossing_different_activations_panics() {
+        let (one, _) = parents(13);
+        let two = Network::new(vec![3, 4, 2], &mut rng::from_seed(14));
+
+        one.crossover(&two, Crossover::Uniform, &mut rng::from_seed(15));
+    }
+
+    #[test]
+    fn node_strengths_follow_each_layers_activation() {
+        let network = Network::with_activations(
+            vec![2, 3, 3],
+            vec![Activation::Relu, Activation::Tanh],
+            &mut rng::from_seed(16),
+        );
+
+        assert_eq!(network.node_strengths(0, &[-4.0, 2.0]), vec![1.0, 0.5]);
+        assert_eq!(
+            network.node_strengths(1, &[0.0, 2.0, 4.0]),
+            vec![0.0, 0.5, 1.0]
+        );
+        assert_eq!(
+            network.node_strengths(2, &[-1.0, 0.0, 1.0]),
+            vec![0.0, 0.5, 1.0]
+        );
+        assert_eq!(network.node_strengths(1, &[0.0; 3]), vec![0.0; 3]);
+    }
+
+    // The loss of `network` on the batch, for comparing against.
+    fn batch_loss(
+        network: &Network,
+        inputs: &Matrix<f64>,
+        targets: &Matrix<f64>,
+        loss: Loss,
+    ) -> f64 {
+        loss.loss(&network.evaluate_batch(inputs), targets)
+    }
+
+    // Checks every gradient of a [3, 4, 2] network against a central finite
+    // difference of the loss.
+    fn check_gradients(hidden: Activation, output: Activation, loss: Loss, seed: u64) {
+        const STEP: f64 = 1e-6;
+
+        let mut rng = rng::from_seed(seed);
+        let network = Network::with_activations(vec![3, 4, 2], vec![hidden, output], &mut rng);
+        let inputs = Matrix::new_map(5, 3, |_, _| rng.sample(StandardNormal));
+        let targets = match loss {
+            Loss::MeanSquaredError => Matrix::new_map(5, 2, |_, _| rng.gen_range(0.0..1.0)),
+            // One-hot, as cross-entropy expects probabilities.
+            Loss::CrossEntropy => Matrix::new_map(5, 2, |row, col| (row % 2 == col) as u8 as f64),
+        };
+
+        let (_, gradients) = network.gradients(&inputs, &targets, loss);
+
+        for layer in 0..network.weights.len() {
+            let parameters = [
+                (false, network.weights[layer].get_shape()),
+                (true, network.biases[layer].get_shape()),
+            ];
+            for &(is_bias, (height, width)) in parameters.iter() {
+                for row in 0..height {
+                    for col in 0..width {
+                        let nudged = |amount: f64| {
+                            let mut network = network.clone();
+                            match is_bias {
+                                false => network.weights[layer][row][col] += amount,
+                                true => network.biases[layer][row][col] += amount,
+                            }
+
+                            batch_loss(&network, &inputs, &targets, loss)
+                        };
+                        let numeric = (nudged(STEP) - nudged(-STEP)) / (2.0 * STEP);
+                        let analytic = match is_bias {
+                            false => gradients.get_weights(layer)[row][col],
+                            true => gradients.get_biases(layer)[row][col],
+                        };
+
+                        assert!(
+                            (numeric - analytic).abs() <= 1e-6 + 1e-4 * numeric.abs(),
+                            "{} {} / {} {} layer {} [{}][{}]: expected {}, got {}",
+                            hidden,
+                            output,
+                            loss,
+                            if is_bias { "bias" } else { "weight" },
+                            layer,
+                            row,
+                            col,
+                            numeric,
+                            analytic
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn gradients_match_finite_differences() {
+        let activations = [
+            Activation::Sigmoid,
+            Activation::Tanh,
+            Activation::Relu,
+            Activation::LeakyRelu { slope: 0.1 },
+            Activation::Elu { alpha: 1.0 },
+            Activation::Softsign,
+            Activation::Linear,
+        ];
+
+        for (seed, &activation) in activations.iter().enumerate() {
+            let seed = seed as u64;
+            check_gradients(
+                activation,
+                Activation::Sigmoid,
+                Loss::MeanSquaredError,
+                seed,
+            );
+            check_gradients(Activation::Tanh, activation, Loss::MeanSquaredError, seed);
+            check_gradients(activation, Activation::Softmax, Loss::CrossEntropy, seed);
+        }
+
+        check_gradients(
+            Activation::Tanh,
+            Activation::Softmax,
+            Loss::MeanSquaredError,
+            7,
+        );
+        check_gradients(Activation::Tanh, Activation::Sigmoid, Loss::CrossEntropy, 8);
+    }
+
+    #[test]
+    #[should_panic(expected = "Cannot compute gradients of an empty batch")]
+    fn gradients_reject_an_empty_batch() {
+        let network = Network::new(vec![3, 4, 2], &mut rng::from_seed(17));
+
+        network.gradients(
+            &Matrix::new(0, 3),
+            &Matrix::new(0, 2),
+            Loss::MeanSquaredError,
+        );
+    }
+
+    #[test]
+    fn crossover_operators_round_trip() {
+        let operators = [
+            Crossover::Uniform,
+            Crossover::Neuron,
+            Crossover::SinglePoint,
+            Crossover::TwoPoint,
+            Crossover::Blend { alpha: 0.25 },
+            Crossover::SimulatedBinary { eta: 15.0 },
+        ];
+
+        for &operator in operators.iter() {
+            assert_eq!(operator.to_string().parse::<Crossover>(), Ok(operator));
+
+            let mut bytes = Vec::new();
+            operator.write_to(&mut bytes).unwrap();
+            let read = Crossover::read_from(&mut bytes.as_slice()).unwrap();
+            assert_eq!(read, operator);
+        }
+
+        assert!("blend:-1".parse::<Crossover>().is_err());
+        assert!("three-point".parse::<Crossover>().is_err());
+    }
+
+    #[test]
+    fn crossover_operators_with_invalid_parameters_are_rejected_on_load() {
+        let operators = [
+            Crossover::Blend { alpha: -0.5 },
+            Crossover::Blend { alpha: f64::NAN },
+            Crossover::Blend {
+                alpha: f64::INFINITY,
+            },
+            Crossover::SimulatedBinary { eta: -1.0 },
+            Crossover::SimulatedBinary { eta: f64::NAN },
+            Crossover::SimulatedBinary { eta: f64::INFINITY },
+        ];
+
+        for &operator in operators.iter() {
+            let mut bytes = Vec::new();
+            operator.write_to(&mut bytes).unwrap();
+            assert!(
+                matches!(
+                    Crossover::read_from(&mut bytes.as_slice()),
+                    Err(FormatError::Invalid(_))
+                ),
+                "{:?}",
+                operator
+            );
+        }
+    }
+
+    fn saved(network: &Network) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        network.write_to(&mut bytes).unwrap();
+
+        bytes
+    }
+
+    #[test]
+    fn networks_round_trip() {
+        let (network, _) = parents(0);
+        let read = Network::read_from(&mut saved(&network).as_slice()).unwrap();
+
+        assert_eq!(read.get_shape(), network.get_shape());
+        assert_eq!(read.get_activations(), network.get_activations());
+        assert_eq!(read.get_genes(), network.get_genes());
+    }
+
+    #[test]
+    fn networks_with_a_bad_header_are_rejected() {
+        let (network, _) = parents(1);
+
+        let mut bad_magic = saved(&network);
+        bad_magic[..4].copy_from_slice(b"SPOP");
+        assert!(matches!(
+            Network::read_from(&mut bad_magic.as_slice()),
+            Err(FormatError::BadMagic { .. })
+        ));
+
+        // The version follows the four byte magic number.
+        let mut bad_version = saved(&network);
+        bad_version[4..8].copy_from_slice(&2u32.to_le_bytes());
+        assert!(matches!(
+            Network::read_from(&mut bad_version.as_slice()),
+            Err(FormatError::UnsupportedVersion {
+                found: 2,
+                latest: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn networks_of_the_wrong_shape_are_rejected() {
+        let (network, _) = parents(2);
+
+        // The hidden layer's size follows the header, the layer count and the
+        // input layer's size, so this leaves the matrices a size short.
+        let mut mismatched = saved(&network);
+        mismatched[20..28].copy_from_slice(&5u64.to_le_bytes());
+        assert!(matches!(
+            Network::read_from(&mut mismatched.as_slice()),
+            Err(FormatError::Invalid(_))
+        ));
+
+        assert!(network.check_io(3, 2).is_ok());
+        assert!(matches!(
+            network.check_io(4, 2),
+            Err(FormatError::WrongShape {
+                expected_inputs: 4,
+                expected_outputs: 2,
+                ..
+            })
+        ));
+    }
+
+    fn random_inputs(count: usize, size: usize, rng: &mut SimRng) -> Vec<Vec<f64>> {
+        (0..count)
+            .map(|_| (0..size).map(|_| rng.sample(StandardNormal)).collect())
+            .collect()
+    }
+
+    #[test]
+    fn evaluating_into_a_reused_workspace_matches_evaluate() {
+        let (one, two) = parents(3);
+        let inputs = random_inputs(6, 3, &mut rng::from_seed(3));
+
+        // Start from a workspace fitted to another shape and batch size, so
+        // it has to refit before it can be reused.
+        let mut workspace = Workspace::new();
+        let other = Network::new(vec![5, 2], &mut rng::from_seed(4));
+        other.evaluate_batch_into(&Matrix::new(4, 5), &mut workspace);
+
+        for (i, input) in inputs.iter().enumerate() {
+            let network = if i % 2 == 0 { &one } else { &two };
+            let output = network.evaluate_into(input, &mut workspace).to_vec();
+            assert_eq!(output, network.evaluate(input.clone()));
+            assert_eq!(workspace.get_layer(0), &input[..]);
+        }
+    }
+
+    #[test]
+    fn each_batch_row_matches_evaluate() {
+        let (network, _) = parents(5);
+        let mut rng = rng::from_seed(5);
+        let mut workspace = Workspace::new();
+
+        for &rows in [1, 7].iter() {
+            let inputs = random_inputs(rows, 3, &mut rng);
+            let batch = Matrix::new_map(rows, 3, |row, col| inputs[row][col]);
+
+            let outputs = network.evaluate_batch(&batch);
+            assert_eq!(outputs.get_shape(), (rows, 2));
+            for (row, input) in inputs.iter().enumerate() {
+                assert_eq!(outputs[row], network.evaluate(input.clone())[..]);
+            }
+
+            let reused = network.evaluate_batch_into(&batch, &mut workspace);
+            for row in 0..rows {
+                assert_eq!(reused[row], outputs[row]);
+            }
+        }
+    }
+}
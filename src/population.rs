@@ -1,79 +1,1026 @@
-use crate::agent::Agent;
+use crate::agent::{Agent, Evaluation, Persist};
+use crate::format::{self, FormatError};
+use crate::network::Crossover;
+use crate::rng::{self, SimRng};
+use crate::selection::SelectionStrategy;
+use crate::stats::GenerationStats;
 
-use rand::seq::SliceRandom;
-use rand::thread_rng;
+use rand::Rng;
 
 use rayon::prelude::*;
 
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Hyperparameters controlling how a population breeds.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BreedingConfig {
+    /// How the parents of each child are picked.
+    pub selection: SelectionStrategy,
+    /// Chance that a child comes from crossing two distinct parents, rather
+    /// than from a single one, before it's mutated.
+    pub crossover_rate: f64,
+    /// How two parents are crossed.
+    pub crossover: Crossover,
+    /// How many of the fittest agents survive unchanged into the next
+    /// generation.
+    pub elite_count: usize,
+    /// How many of the best agents ever seen to remember.
+    pub hall_of_fame_size: usize,
+    /// Whether hall of fame members can be picked as parents, alongside the
+    /// current generation.
+    pub breed_from_hall_of_fame: bool,
+}
+
+impl Default for BreedingConfig {
+    fn default() -> Self {
+        Self {
+            selection: SelectionStrategy::default(),
+            crossover_rate: 0.5,
+            crossover: Crossover::default(),
+            elite_count: 1,
+            hall_of_fame_size: 10,
+            breed_from_hall_of_fame: false,
+        }
+    }
+}
+
+impl BreedingConfig {
+    // How many times to pick a mate again before giving up on finding one
+    // distinct from the first parent, e.g. when a single agent dominates.
+    const MATE_ATTEMPTS: usize = 10;
+
+    fn write_to(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        self.selection.write_to(writer)?;
+        format::write_f64(writer, self.crossover_rate)?;
+        self.crossover.write_to(writer)?;
+        format::write_u64(writer, self.elite_count as u64)?;
+        format::write_u64(writer, self.hall_of_fame_size as u64)?;
+        format::write_u8(writer, self.breed_from_hall_of_fame.into())
+    }
+
+    /// Checks the settings that don't depend on the population: the
+    /// selection and crossover parameters, and a crossover rate that's a
+    /// probability. Elites must also leave room for children, see
+    /// `Population::read_from`.
+    pub fn validate(&self) -> Result<(), String> {
+        self.selection.validate()?;
+        self.crossover.validate()?;
+
+        if !(0.0..=1.0).contains(&self.crossover_rate) {
+            return Err(format!(
+                "Crossover rate must be between 0 and 1, got {}",
+                self.crossover_rate
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn read_from(reader: &mut impl Read) -> Result<Self, FormatError> {
+        let config = Self {
+            selection: SelectionStrategy::read_from(reader)?,
+            crossover_rate: format::read_f64(reader)?,
+            crossover: Crossover::read_from(reader)?,
+            elite_count: format::read_usize(reader)?,
+            hall_of_fame_size: format::read_usize(reader)?,
+            breed_from_hall_of_fame: match format::read_u8(reader)? {
+                0 => false,
+                1 => true,
+                other => {
+                    return Err(FormatError::Invalid(format!(
+                        "{} is not a valid hall of fame breeding flag",
+                        other
+                    )))
+                }
+            },
+        };
+        config.validate().map_err(FormatError::Invalid)?;
+
+        Ok(config)
+    }
+}
+
 #[derive(Debug)]
 pub struct Population<T>
 where
     T: Agent,
 {
     agents: Vec<T>,
+    evaluations: Vec<Option<Evaluation>>,
+    // The best agents ever seen and their fitness, fittest first.
+    hall_of_fame: Vec<(T, f64)>,
+    rng: SimRng,
+    evaluation_seed: u64,
+    generation: usize,
+    config: BreedingConfig,
+    // Wall-clock time spent evaluating this generation, and breeding it from
+    // the last. Only kept for stats, so not saved with checkpoints.
+    evaluation_time: Duration,
+    breeding_time: Duration,
 }
 
 impl<T> Population<T>
 where
-    T: Agent + Sync,
+    T: Agent + Sync + Send,
 {
-    pub fn new(agents: Vec<T>) -> Self {
-        Population { agents }
+    /// Creates a population whose breeding and evaluation are all driven by
+    /// `seed`. Every agent in a generation is evaluated against the same seed,
+    /// so fitness values are comparable across agents, while each generation
+    /// draws a fresh one so agents can't overfit to a single set of games.
+    pub fn new(agents: Vec<T>, seed: u64) -> Self {
+        Self::with_config(agents, seed, BreedingConfig::default())
+    }
+
+    pub fn with_config(agents: Vec<T>, seed: u64, config: BreedingConfig) -> Self {
+        let mut rng = rng::from_seed(seed);
+        let evaluation_seed = rng.gen();
+
+        Population {
+            evaluations: vec![None; agents.len()],
+            agents,
+            hall_of_fame: Vec::new(),
+            rng,
+            evaluation_seed,
+            generation: 0,
+            config,
+            evaluation_time: Duration::ZERO,
+            breeding_time: Duration::ZERO,
+        }
+    }
+
+    /// The seed this generation's agents are evaluated against.
+    pub fn get_evaluation_seed(&self) -> u64 {
+        self.evaluation_seed
     }
 
-    pub fn get_best(&self) -> (&T, f64) {
-        let (best, score) = self
-            .agents
+    /// How many times this population has been bred.
+    pub fn get_generation(&self) -> usize {
+        self.generation
+    }
+
+    pub fn get_config(&self) -> &BreedingConfig {
+        &self.config
+    }
+
+    pub fn get_agents(&self) -> &[T] {
+        &self.agents
+    }
+
+    /// How each agent did, in the same order as `get_agents`, or `None` for
+    /// agents that haven't been evaluated yet.
+    pub fn get_evaluations(&self) -> &[Option<Evaluation>] {
+        &self.evaluations
+    }
+
+    fn get_fitness(&self, agent: usize) -> Option<f64> {
+        self.evaluations[agent].map(|evaluation| evaluation.fitness)
+    }
+
+    /// The best agents from every generation bred so far, along with their
+    /// fitness, fittest first.
+    pub fn get_hall_of_fame(&self) -> &[(T, f64)] {
+        &self.hall_of_fame
+    }
+
+    /// Evaluates every agent that hasn't been already. Evaluations are
+    /// cached until the agent is replaced, so this is cheap to call again.
+    pub fn evaluate(&mut self) {
+        let start = Instant::now();
+        let evaluation_seed = self.evaluation_seed;
+        self.agents
             .par_iter()
-            .map(|agent_ref: &T| (agent_ref, agent_ref.fitness()))
-            .max_by(|&(_, score1): &(&T, f64), &(_, score2): &(&T, f64)| {
-                score1.partial_cmp(&score2).unwrap()
-            })
-            .unwrap();
+            .zip(self.evaluations.par_iter_mut())
+            .filter(|(_, evaluation)| evaluation.is_none())
+            .for_each(|(agent, evaluation)| *evaluation = Some(agent.evaluate(evaluation_seed)));
+        self.evaluation_time += start.elapsed();
+    }
 
-        (best, score)
+    /// The fittest of the agents that have been evaluated, along with its
+    /// fitness.
+    pub fn get_best(&self) -> Option<(&T, f64)> {
+        self.agents
+            .iter()
+            .zip(self.evaluations.iter())
+            .filter_map(|(agent, &evaluation)| Some((agent, evaluation?.fitness)))
+            .max_by(|&(_, score1), &(_, score2)| score1.partial_cmp(&score2).unwrap())
     }
 
-    pub fn breed(&self) -> Self {
+    /// Evaluates any agents that still need it, then breeds the next
+    /// generation from the fittest, remembering the best of them in the hall
+    /// of fame. Takes the population by value, so no generation can be bred
+    /// twice.
+    pub fn breed(mut self) -> Self {
         if self.agents.len() < 2 {
             panic!("Cannot breed with less than 2 agents");
         }
 
-        let mut rng = thread_rng();
+        self.evaluate();
+        let start = Instant::now();
+        self.update_hall_of_fame();
 
-        let mut new_agents: Vec<T> = Vec::new();
-        let mut agent_scores: Vec<(&T, f64)> = self
-            .agents
-            .par_iter()
-            .map(|agent_ref: &T| (agent_ref, agent_ref.fitness()))
-            .collect();
+        let mut rng = self.rng.clone();
+        let generation_seed: u64 = rng.gen();
+        let evaluation_seed = rng.gen();
 
-        agent_scores.par_sort_unstable_by(|&(_, val1): &(&T, f64), &(_, val2): &(&T, f64)| {
-            val1.partial_cmp(&val2).unwrap()
-        });
+        let mut pool_fitness = (0..self.agents.len())
+            .map(|i| self.get_fitness(i).unwrap())
+            .collect::<Vec<f64>>();
+        let mut pool = self.agents.iter().collect::<Vec<&T>>();
+        if self.config.breed_from_hall_of_fame {
+            // Members still in this generation, like the elites, are already
+            // in the pool, and a second copy could end up mated with itself.
+            for (agent, fitness) in self.hall_of_fame.iter() {
+                if !self.contains(agent, *fitness) {
+                    pool.push(agent);
+                    pool_fitness.push(*fitness);
+                }
+            }
+        }
 
-        let percentage_top_agents = 0.1;
-        let num_top_agents = (percentage_top_agents * (agent_scores.len() as f64)) as usize;
-        let agents_to_breed_with = agent_scores
+        // The elites survive unchanged, so their evaluations carry over.
+        let elite_count = usize::min(self.config.elite_count, self.agents.len());
+        let elites = self.fittest(elite_count);
+        let mut new_agents = elites
             .iter()
-            .rev()
-            .take(num_top_agents)
-            .map(|&(agent_ref, val): &(&T, f64)| agent_ref)
-            .collect::<Vec<_>>();
-
-        new_agents.push(agents_to_breed_with[0].clone());
-        while new_agents.len() < self.agents.len() {
-            let agent1 = *agents_to_breed_with.choose(&mut rng).unwrap();
-            /*
-                        let mut agent2 = agent1;
-                    while (agent1 as *const T) == (agent2 as *const T) {
-                    agent2 = agents_to_breed_with.choose(&mut rng).unwrap();
+            .map(|&elite| self.agents[elite].clone())
+            .collect::<Vec<T>>();
+
+        let parents = self.pick_parents(&pool_fitness, self.agents.len() - elite_count, &mut rng);
+        new_agents.par_extend(parents.par_iter().enumerate().map(|(i, &(parent, mate))| {
+            // Each child gets its own stream, so the result doesn't depend on scheduling.
+            let mut child_rng = rng::sub_stream(generation_seed, (elite_count + i) as u64);
+
+            let parent = pool[parent];
+            match mate {
+                Some(mate) => parent
+                    .crossover(pool[mate], self.config.crossover, &mut child_rng)
+                    .mutate(&mut child_rng),
+                None => parent.mutate(&mut child_rng),
+            }
+        }));
+
+        let mut evaluations = vec![None; new_agents.len()];
+        for (i, &elite) in elites.iter().enumerate() {
+            evaluations[i] = self.evaluations[elite];
+        }
+
+        Self {
+            agents: new_agents,
+            evaluations,
+            hall_of_fame: self.hall_of_fame,
+            rng,
+            evaluation_seed,
+            generation: self.generation + 1,
+            config: self.config,
+            evaluation_time: Duration::ZERO,
+            breeding_time: start.elapsed(),
+        }
+    }
+
+    /// Statistics about this generation, once every agent has been evaluated.
+    pub fn get_stats(&self) -> Option<GenerationStats> {
+        let mut fitness = (0..self.agents.len())
+            .map(|i| self.get_fitness(i))
+            .collect::<Option<Vec<f64>>>()?;
+        fitness.sort_by(|fitness1, fitness2| fitness1.partial_cmp(fitness2).unwrap());
+
+        let count = fitness.len() as f64;
+        let mean_fitness = fitness.iter().sum::<f64>() / count;
+        let variance = fitness
+            .iter()
+            .map(|&val| (val - mean_fitness) * (val - mean_fitness))
+            .sum::<f64>()
+            / count;
+        let middle = fitness.len() / 2;
+        let median_fitness = if fitness.len() % 2 == 0 {
+            (fitness[middle - 1] + fitness[middle]) / 2.0
+        } else {
+            fitness[middle]
+        };
+
+        let evaluations = self
+            .evaluations
+            .iter()
+            .map(|evaluation| evaluation.unwrap());
+        let steps_survived = evaluations
+            .clone()
+            .map(|evaluation| evaluation.steps_survived)
+            .collect::<Option<Vec<f64>>>();
+
+        Some(GenerationStats {
+            generation: self.generation + 1,
+            best_fitness: *fitness.last().unwrap(),
+            mean_fitness,
+            median_fitness,
+            worst_fitness: fitness[0],
+            fitness_std_dev: variance.sqrt(),
+            best_game_score: evaluations
+                .filter_map(|evaluation| evaluation.game_score)
+                .max(),
+            mean_steps_survived: steps_survived
+                .map(|steps_survived| steps_survived.iter().sum::<f64>() / count),
+            diversity: self.diversity(),
+            evaluation_time: self.evaluation_time,
+            breeding_time: self.breeding_time,
+        })
+    }
+
+    // How much the population's genomes vary: the standard deviation of each
+    // gene across the population, averaged over every gene.
+    fn diversity(&self) -> f64 {
+        let mut sums: Vec<f64> = Vec::new();
+        let mut squared_sums: Vec<f64> = Vec::new();
+        for agent in self.agents.iter() {
+            let genome = agent.genome();
+            sums.resize(genome.len(), 0.0);
+            squared_sums.resize(genome.len(), 0.0);
+            for (i, gene) in genome.into_iter().enumerate() {
+                sums[i] += gene;
+                squared_sums[i] += gene * gene;
+            }
+        }
+
+        if sums.is_empty() {
+            return 0.0;
+        }
+
+        let count = self.agents.len() as f64;
+        let total_std_dev: f64 = sums
+            .iter()
+            .zip(squared_sums.iter())
+            .map(|(&sum, &squared_sum)| {
+                let mean = sum / count;
+                // Rounding can leave the variance of a constant gene just below zero.
+                f64::max(squared_sum / count - mean * mean, 0.0).sqrt()
+            })
+            .sum();
+
+        total_std_dev / (sums.len() as f64)
+    }
+
+    // Indices of the `count` fittest evaluated agents, fittest first. Of
+    // agents with equal fitness, later ones come first, as with `get_best`.
+    fn fittest(&self, count: usize) -> Vec<usize> {
+        let mut ranked = (0..self.agents.len())
+            .filter(|&i| self.evaluations[i].is_some())
+            .collect::<Vec<usize>>();
+        ranked.sort_by(|&i, &j| {
+            self.get_fitness(i)
+                .partial_cmp(&self.get_fitness(j))
+                .unwrap()
+        });
+        ranked.reverse();
+        ranked.truncate(count);
+
+        ranked
+    }
+
+    // Whether this generation has an agent identical to `agent`, which has
+    // `fitness`. Evaluation is deterministic, so only agents with the same
+    // fitness need their genomes compared.
+    fn contains(&self, agent: &T, fitness: f64) -> bool {
+        let genome = agent.genome();
+
+        (0..self.agents.len())
+            .filter(|&i| self.get_fitness(i) == Some(fitness))
+            .any(|i| self.agents[i].genome() == genome)
+    }
+
+    // Adds this generation's best newcomers to the hall of fame. Elites
+    // carried over from the last generation were already considered then,
+    // so they're skipped rather than being added again.
+    fn update_hall_of_fame(&mut self) {
+        let size = self.config.hall_of_fame_size;
+        let carried_over = if self.generation == 0 {
+            0
+        } else {
+            usize::min(self.config.elite_count, self.agents.len())
+        };
+
+        let newcomers = self
+            .fittest(self.agents.len())
+            .into_iter()
+            .filter(|&i| i >= carried_over)
+            .take(size)
+            .collect::<Vec<usize>>();
+        for i in newcomers {
+            self.hall_of_fame
+                .push((self.agents[i].clone(), self.get_fitness(i).unwrap()));
+        }
+
+        // Stable, so on a tie the longer standing member stays ahead.
+        self.hall_of_fame
+            .sort_by(|(_, score1), (_, score2)| score2.partial_cmp(score1).unwrap());
+        self.hall_of_fame.truncate(size);
+    }
+
+    // Picks the parents of `count` children, as indices into `fitness`.
+    // Children bred by crossover also get a mate, which is never the same
+    // agent as their first parent.
+    fn pick_parents(
+        &self,
+        fitness: &[f64],
+        count: usize,
+        rng: &mut SimRng,
+    ) -> Vec<(usize, Option<usize>)> {
+        let selection = &self.config.selection;
+        let parents = selection.select(fitness, count, rng);
+        let crossed = (0..count)
+            .filter(|_| rng.gen_bool(self.config.crossover_rate))
+            .collect::<Vec<usize>>();
+
+        // Every mate comes from a single selection, the same as the parents,
+        // so strategies like stochastic universal sampling keep their spread.
+        let mut mates = selection
+            .select(fitness, crossed.len(), rng)
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<Option<usize>>>();
+
+        // A mate that's its own parent swaps with another child's mate where
+        // that suits both children, and is otherwise picked again.
+        for i in 0..crossed.len() {
+            let parent = parents[crossed[i]];
+            if mates[i] != Some(parent) {
+                continue;
+            }
+
+            let swap = (0..crossed.len())
+                .find(|&j| mates[j] != Some(parent) && Some(parents[crossed[j]]) != mates[i]);
+            match swap {
+                Some(j) => mates.swap(i, j),
+                None => {
+                    mates[i] = (0..BreedingConfig::MATE_ATTEMPTS)
+                        .map(|_| selection.select(fitness, 1, rng)[0])
+                        .find(|&mate| mate != parent);
+                }
+            }
+        }
+
+        let mut picked = parents
+            .into_iter()
+            .map(|parent| (parent, None))
+            .collect::<Vec<(usize, Option<usize>)>>();
+        for (&child, mate) in crossed.iter().zip(mates) {
+            picked[child].1 = mate;
+        }
+
+        picked
+    }
+}
+
+impl<T> Population<T>
+where
+    T: Agent + Persist + Sync + Send,
+{
+    const MAGIC: &'static [u8; 4] = b"SPOP";
+    const FORMAT_VERSION: u32 = 1;
+
+    /// Writes everything needed to carry on breeding exactly where this
+    /// population left off. The file is replaced atomically, so a crash while
+    /// saving leaves the previous checkpoint intact.
+    pub fn save_checkpoint(&self, path: impl AsRef<Path>) -> Result<(), FormatError> {
+        let path = path.as_ref();
+        let temp_path = path.with_extension("tmp");
+
+        let mut writer = BufWriter::new(File::create(&temp_path)?);
+        self.write_to(&mut writer)?;
+        writer
+            .into_inner()
+            .map_err(|err| err.into_error())?
+            .sync_all()?;
+        fs::rename(&temp_path, path)?;
+
+        Ok(())
+    }
+
+    pub fn load_checkpoint(path: impl AsRef<Path>) -> Result<Self, FormatError> {
+        Self::read_from(&mut BufReader::new(File::open(path)?))
+    }
+
+    /// Writes the population in the following format, all little-endian:
+    ///
+    /// ```text
+    /// magic            b"SPOP"
+    /// version          u32, currently 1
+    /// generation       u64
+    /// evaluation seed  u64
+    /// rng state        see `rng::write_state`
+    /// breeding config  the selection strategy, as a tag byte and then any
+    ///                  parameter: a fraction (f64) or tournament size (u64),
+    ///                  followed by the crossover rate (f64), the crossover
+    ///                  operator as a tag byte and then any parameter: alpha
+    ///                  or eta (f64), elite count (u64), hall of fame size
+    ///                  (u64) and a byte that's 1 if the hall of fame breeds
+    /// agent count      u64
+    /// agents           see the agent's `Persist` impl
+    /// evaluations      per agent, a byte that's 1 if it's been evaluated,
+    ///                  followed by its evaluation if so, see
+    ///                  `Evaluation::write_to`
+    /// hall of fame     member count (u64), then each member as the agent
+    ///                  followed by its fitness (f64)
+    /// ```
+    pub fn write_to(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        format::write_header(writer, Self::MAGIC, Self::FORMAT_VERSION)?;
+        format::write_u64(writer, self.generation as u64)?;
+        format::write_u64(writer, self.evaluation_seed)?;
+        rng::write_state(&self.rng, writer)?;
+        self.config.write_to(writer)?;
+
+        format::write_u64(writer, self.agents.len() as u64)?;
+        for agent in self.agents.iter() {
+            agent.write_to(writer)?;
+        }
+
+        for evaluation in self.evaluations.iter() {
+            match evaluation {
+                Some(evaluation) => {
+                    format::write_u8(writer, 1)?;
+                    evaluation.write_to(writer)?;
                 }
-            */
+                None => format::write_u8(writer, 0)?,
+            }
+        }
+
+        format::write_u64(writer, self.hall_of_fame.len() as u64)?;
+        for (agent, fitness) in self.hall_of_fame.iter() {
+            agent.write_to(writer)?;
+            format::write_f64(writer, *fitness)?;
+        }
+
+        Ok(())
+    }
+
+    /// Reads a population written by `write_to`, rejecting any whose config
+    /// it couldn't be bred with.
+    pub fn read_from(reader: &mut impl Read) -> Result<Self, FormatError> {
+        format::read_header(reader, Self::MAGIC, Self::FORMAT_VERSION)?;
+        let generation = format::read_usize(reader)?;
+        let evaluation_seed = format::read_u64(reader)?;
+        let rng = rng::read_state(reader)?;
+        let config = BreedingConfig::read_from(reader)?;
+
+        let agent_count = format::read_usize(reader)?;
+        if config.elite_count >= agent_count {
+            return Err(FormatError::Invalid(format!(
+                "{} elites leave no room for children in a population of {}",
+                config.elite_count, agent_count
+            )));
+        }
+        let agents = (0..agent_count)
+            .map(|_| T::read_from(reader))
+            .collect::<Result<Vec<T>, FormatError>>()?;
+
+        let evaluations = (0..agent_count)
+            .map(|_| match format::read_u8(reader)? {
+                0 => Ok(None),
+                1 => Ok(Some(Evaluation::read_from(reader)?)),
+                other => Err(FormatError::Invalid(format!(
+                    "{} is not a valid fitness flag",
+                    other
+                ))),
+            })
+            .collect::<Result<Vec<Option<Evaluation>>, FormatError>>()?;
+
+        let member_count = format::read_usize(reader)?;
+        let hall_of_fame = (0..member_count)
+            .map(|_| Ok((T::read_from(reader)?, format::read_f64(reader)?)))
+            .collect::<Result<Vec<(T, f64)>, FormatError>>()?;
+
+        Ok(Self {
+            agents,
+            evaluations,
+            hall_of_fame,
+            rng,
+            evaluation_seed,
+            generation,
+            config,
+            evaluation_time: Duration::ZERO,
+            breeding_time: Duration::ZERO,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand_distr::StandardNormal;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    // An agent whose fitness is how close its genes are to zero. Mutation
+    // nudges every gene, so no two agents are ever identical by chance.
+    #[derive(Clone, Debug)]
+    struct Point(Vec<f64>);
+
+    impl Agent for Point {
+        fn evaluate(&self, _seed: u64) -> Evaluation {
+            Evaluation::from_fitness(-self.0.iter().map(|gene| gene * gene).sum::<f64>())
+        }
+
+        fn crossover(&self, other: &Self, _operator: Crossover, _rng: &mut SimRng) -> Self {
+            Point(
+                self.0
+                    .iter()
+                    .zip(other.0.iter())
+                    .map(|(gene1, gene2)| (gene1 + gene2) / 2.0)
+                    .collect(),
+            )
+        }
+
+        fn mutate(&self, rng: &mut SimRng) -> Self {
+            Point(
+                self.0
+                    .iter()
+                    .map(|gene| gene + 0.1 * rng.sample::<f64, _>(StandardNormal))
+                    .collect(),
+            )
+        }
+
+        fn genome(&self) -> Vec<f64> {
+            self.0.clone()
+        }
+    }
+
+    impl Persist for Point {
+        fn write_to(&self, writer: &mut impl Write) -> std::io::Result<()> {
+            format::write_u64(writer, self.0.len() as u64)?;
+            for &gene in self.0.iter() {
+                format::write_f64(writer, gene)?;
+            }
+
+            Ok(())
+        }
+
+        fn read_from(reader: &mut impl Read) -> Result<Self, FormatError> {
+            let len = format::read_usize(reader)?;
+            let genes = (0..len)
+                .map(|_| format::read_f64(reader))
+                .collect::<std::io::Result<Vec<f64>>>()?;
+
+            Ok(Point(genes))
+        }
+    }
+
+    fn population(size: usize, config: BreedingConfig) -> Population<Point> {
+        let mut rng = rng::from_seed(0);
+        let agents = (0..size)
+            .map(|_| Point((0..4).map(|_| rng.sample(StandardNormal)).collect()))
+            .collect();
 
-            new_agents.push(agent1.mutate());
+        Population::with_config(agents, 1, config)
+    }
+
+    #[test]
+    fn hall_of_fame_holds_each_agent_once() {
+        let config = BreedingConfig {
+            elite_count: 3,
+            hall_of_fame_size: 5,
+            breed_from_hall_of_fame: true,
+            ..BreedingConfig::default()
+        };
+        let mut population = population(20, config);
+
+        for _ in 0..10 {
+            population = population.breed();
+
+            let hall_of_fame = population.get_hall_of_fame();
+            assert_eq!(hall_of_fame.len(), 5);
+            for (i, (member, _)) in hall_of_fame.iter().enumerate() {
+                for (other, _) in hall_of_fame[(i + 1)..].iter() {
+                    assert_ne!(member.genome(), other.genome());
+                }
+            }
         }
+    }
+
+    #[test]
+    fn hall_of_fame_members_still_in_the_generation_are_recognised() {
+        let mut population = population(10, BreedingConfig::default());
+        population.evaluate();
 
-        Self { agents: new_agents }
+        let (best, fitness) = population.get_best().unwrap();
+        let best = best.clone();
+        assert!(population.contains(&best, fitness));
+
+        let mutated = best.mutate(&mut rng::from_seed(2));
+        let mutated_fitness = mutated.evaluate(0).fitness;
+        assert!(!population.contains(&mutated, mutated_fitness));
+    }
+
+    #[test]
+    fn mates_are_never_their_own_parent() {
+        let strategies = [
+            SelectionStrategy::Truncation { fraction: 0.2 },
+            SelectionStrategy::Tournament { size: 3 },
+            SelectionStrategy::Roulette,
+            SelectionStrategy::Rank,
+            SelectionStrategy::StochasticUniversal,
+        ];
+        let fitness = (0..10).map(f64::from).collect::<Vec<f64>>();
+
+        for &selection in strategies.iter() {
+            let config = BreedingConfig {
+                selection,
+                crossover_rate: 1.0,
+                ..BreedingConfig::default()
+            };
+            let population = population(10, config);
+            let parents = population.pick_parents(&fitness, 200, &mut rng::from_seed(3));
+
+            assert_eq!(parents.len(), 200);
+            for (parent, mate) in parents {
+                assert_ne!(Some(parent), mate, "{}", selection);
+                assert!(mate.is_some(), "{}", selection);
+            }
+        }
+    }
+
+    #[test]
+    fn stochastic_universal_mates_keep_their_spread() {
+        let config = BreedingConfig {
+            selection: SelectionStrategy::StochasticUniversal,
+            crossover_rate: 1.0,
+            ..BreedingConfig::default()
+        };
+        let population = population(4, config);
+        // Shifted so the least fit has no weight, leaving weights of 1, 2 and 3.
+        let fitness = [1.0, 2.0, 3.0, 4.0];
+        let parents = population.pick_parents(&fitness, 600, &mut rng::from_seed(4));
+
+        let mut mate_counts = [0; 4];
+        for (_, mate) in parents {
+            mate_counts[mate.unwrap()] += 1;
+        }
+        for (agent, &expected) in [0, 100, 200, 300].iter().enumerate() {
+            let count: i32 = mate_counts[agent];
+            assert!((count - expected).abs() <= 1, "{:?}", mate_counts);
+        }
+    }
+
+    #[test]
+    fn elites_survive_with_their_evaluations() {
+        let config = BreedingConfig {
+            elite_count: 2,
+            ..BreedingConfig::default()
+        };
+        let mut population = population(10, config);
+        population.evaluate();
+        let fittest = population
+            .fittest(2)
+            .into_iter()
+            .map(|i| population.get_agents()[i].genome())
+            .collect::<Vec<Vec<f64>>>();
+
+        let bred = population.breed();
+        for (elite, genome) in fittest.iter().enumerate() {
+            assert_eq!(&bred.get_agents()[elite].genome(), genome);
+            assert!(bred.get_evaluations()[elite].is_some());
+        }
+        assert!(bred.get_evaluations()[2..].iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn same_seed_breeds_identical_generations() {
+        // Timings are the only part of the stats that can differ between runs.
+        fn untimed(stats: GenerationStats) -> GenerationStats {
+            GenerationStats {
+                evaluation_time: Duration::ZERO,
+                breeding_time: Duration::ZERO,
+                ..stats
+            }
+        }
+
+        let config = BreedingConfig {
+            elite_count: 2,
+            ..BreedingConfig::default()
+        };
+        let mut population1 = population(10, config);
+        let mut population2 = population(10, config);
+        let mut evaluation_seeds = Vec::new();
+
+        for _ in 0..5 {
+            population1.evaluate();
+            population2.evaluate();
+
+            assert_eq!(
+                population1.get_evaluation_seed(),
+                population2.get_evaluation_seed()
+            );
+            for (agent1, agent2) in population1
+                .get_agents()
+                .iter()
+                .zip(population2.get_agents())
+            {
+                let genome1 = agent1
+                    .genome()
+                    .iter()
+                    .map(|gene| gene.to_bits())
+                    .collect::<Vec<u64>>();
+                let genome2 = agent2
+                    .genome()
+                    .iter()
+                    .map(|gene| gene.to_bits())
+                    .collect::<Vec<u64>>();
+                assert_eq!(genome1, genome2);
+            }
+            assert_eq!(
+                untimed(population1.get_stats().unwrap()),
+                untimed(population2.get_stats().unwrap())
+            );
+
+            evaluation_seeds.push(population1.get_evaluation_seed());
+            population1 = population1.breed();
+            population2 = population2.breed();
+        }
+
+        evaluation_seeds.sort_unstable();
+        evaluation_seeds.dedup();
+        assert_eq!(
+            evaluation_seeds.len(),
+            5,
+            "every generation gets its own evaluation seed"
+        );
+    }
+
+    #[test]
+    fn invalid_breeding_configs_are_rejected_on_load() {
+        let invalid = [
+            BreedingConfig {
+                crossover_rate: f64::NAN,
+                ..BreedingConfig::default()
+            },
+            BreedingConfig {
+                crossover_rate: 1.5,
+                ..BreedingConfig::default()
+            },
+            BreedingConfig {
+                selection: SelectionStrategy::Truncation { fraction: 0.0 },
+                ..BreedingConfig::default()
+            },
+            BreedingConfig {
+                selection: SelectionStrategy::Tournament { size: 0 },
+                ..BreedingConfig::default()
+            },
+        ];
+
+        for config in invalid.iter() {
+            let mut bytes = Vec::new();
+            config.write_to(&mut bytes).unwrap();
+            assert!(
+                matches!(
+                    BreedingConfig::read_from(&mut bytes.as_slice()),
+                    Err(FormatError::Invalid(_))
+                ),
+                "{:?}",
+                config
+            );
+        }
+    }
+
+    #[test]
+    fn populations_without_room_for_children_are_rejected_on_load() {
+        let config = BreedingConfig {
+            elite_count: 4,
+            ..BreedingConfig::default()
+        };
+        let mut bytes = Vec::new();
+        population(4, config).write_to(&mut bytes).unwrap();
+
+        assert!(matches!(
+            Population::<Point>::read_from(&mut bytes.as_slice()),
+            Err(FormatError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn checkpoints_round_trip() {
+        let config = BreedingConfig {
+            selection: SelectionStrategy::Tournament { size: 3 },
+            crossover: Crossover::Blend { alpha: 0.25 },
+            elite_count: 2,
+            hall_of_fame_size: 3,
+            breed_from_hall_of_fame: true,
+            ..BreedingConfig::default()
+        };
+        let mut population = population(10, config).breed().breed();
+        population.evaluate();
+
+        let path =
+            std::env::temp_dir().join(format!("neural-checkpoint-{}.pop", std::process::id()));
+        population.save_checkpoint(&path).unwrap();
+        let loaded = Population::<Point>::load_checkpoint(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.get_generation(), population.get_generation());
+        assert_eq!(
+            loaded.get_evaluation_seed(),
+            population.get_evaluation_seed()
+        );
+        assert_eq!(loaded.get_config(), population.get_config());
+        assert_eq!(loaded.rng, population.rng);
+        for (agent1, agent2) in loaded.get_agents().iter().zip(population.get_agents()) {
+            assert_eq!(agent1.genome(), agent2.genome());
+        }
+        assert_eq!(loaded.get_agents().len(), population.get_agents().len());
+        assert_eq!(loaded.get_evaluations(), population.get_evaluations());
+        let hall_of_fame = |population: &Population<Point>| {
+            population
+                .get_hall_of_fame()
+                .iter()
+                .map(|(member, fitness)| (member.genome(), *fitness))
+                .collect::<Vec<(Vec<f64>, f64)>>()
+        };
+        assert_eq!(hall_of_fame(&loaded), hall_of_fame(&population));
+    }
+
+    // A `Point` that counts how many times agents sharing its counter have
+    // been evaluated.
+    #[derive(Clone, Debug)]
+    struct Counted {
+        point: Point,
+        evaluations: Arc<AtomicUsize>,
+    }
+
+    impl Agent for Counted {
+        fn evaluate(&self, seed: u64) -> Evaluation {
+            self.evaluations.fetch_add(1, Ordering::SeqCst);
+            self.point.evaluate(seed)
+        }
+
+        fn crossover(&self, other: &Self, operator: Crossover, rng: &mut SimRng) -> Self {
+            Counted {
+                point: self.point.crossover(&other.point, operator, rng),
+                evaluations: Arc::clone(&self.evaluations),
+            }
+        }
+
+        fn mutate(&self, rng: &mut SimRng) -> Self {
+            Counted {
+                point: self.point.mutate(rng),
+                evaluations: Arc::clone(&self.evaluations),
+            }
+        }
+
+        fn genome(&self) -> Vec<f64> {
+            self.point.genome()
+        }
+    }
+
+    #[test]
+    fn agents_are_evaluated_once_per_generation() {
+        let evaluations = Arc::new(AtomicUsize::new(0));
+        let agents = population(10, BreedingConfig::default())
+            .get_agents()
+            .iter()
+            .map(|point| Counted {
+                point: point.clone(),
+                evaluations: Arc::clone(&evaluations),
+            })
+            .collect();
+        let config = BreedingConfig {
+            elite_count: 3,
+            ..BreedingConfig::default()
+        };
+        let mut population = Population::with_config(agents, 1, config);
+
+        population.evaluate();
+        population.evaluate();
+        assert_eq!(evaluations.load(Ordering::SeqCst), 10);
+
+        for generation in 1..=5 {
+            let elites = population
+                .fittest(3)
+                .into_iter()
+                .map(|i| {
+                    (
+                        population.get_agents()[i].genome(),
+                        population.get_evaluations()[i],
+                    )
+                })
+                .collect::<Vec<(Vec<f64>, Option<Evaluation>)>>();
+
+            population = population.breed();
+            population.evaluate();
+            population.evaluate();
+            assert_eq!(evaluations.load(Ordering::SeqCst), 10 + 7 * generation);
+
+            // Elites keep the scores they already had, and every child is
+            // scored afresh.
+            let agents = population.get_agents();
+            let scores = population.get_evaluations();
+            for (elite, (genome, evaluation)) in elites.iter().enumerate() {
+                assert_eq!(&agents[elite].genome(), genome);
+                assert_eq!(&scores[elite], evaluation);
+            }
+            for (child, score) in agents[3..].iter().zip(scores[3..].iter()) {
+                assert_eq!(*score, Some(child.point.evaluate(0)));
+            }
+        }
     }
 }
@@ -0,0 +1,369 @@
+#[cfg(feature = "gui")]
+use sdl2::rect::{Point, Rect};
+#[cfg(feature = "gui")]
+use sdl2::render::Canvas;
+#[cfg(feature = "gui")]
+use sdl2::video::Window;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Color {
+    pub const BLACK: Color = Color::rgb(0, 0, 0);
+    pub const WHITE: Color = Color::rgb(255, 255, 255);
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+}
+
+/// The handful of drawing primitives the game and network views are built
+/// from. Coordinates are in pixels, with the origin in the top left.
+pub trait Renderer {
+    fn clear(&mut self, color: Color);
+    fn fill_rect(&mut self, x: i32, y: i32, width: u32, height: u32, color: Color);
+    fn draw_line(&mut self, start: (i32, i32), end: (i32, i32), color: Color);
+    fn present(&mut self);
+}
+
+/// Draws `text` with its top left corner at (`x`, `y`), in a tiny built-in
+/// font where each character is 3x5 blocks of `block_size` pixels. Letters
+/// are all drawn as capitals, and characters the font lacks as `?`.
+pub fn draw_text(
+    renderer: &mut impl Renderer,
+    text: &str,
+    x: i32,
+    y: i32,
+    block_size: u32,
+    color: Color,
+) {
+    let block = block_size as i32;
+    for (i, character) in text.chars().enumerate() {
+        let left = x + (i as i32) * 4 * block;
+        for (row, bits) in glyph(character).iter().enumerate() {
+            for col in 0..3 {
+                if bits & (0b100 >> col) != 0 {
+                    renderer.fill_rect(
+                        left + col * block,
+                        y + (row as i32) * block,
+                        block_size,
+                        block_size,
+                        color,
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Width in pixels of `text` as drawn by `draw_text`.
+pub fn text_width(text: &str, block_size: u32) -> u32 {
+    match text.chars().count() as u32 {
+        0 => 0,
+        len => (4 * len - 1) * block_size,
+    }
+}
+
+// Each row of a character, top first, with the leftmost block in the highest of three bits.
+fn glyph(character: char) -> [u8; 5] {
+    match character.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b011, 0b100, 0b100, 0b100, 0b011],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b011, 0b100, 0b101, 0b101, 0b011],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'I' => [0b111, 0b010, 0b010, 0b010, 0b111],
+        'J' => [0b001, 0b001, 0b001, 0b101, 0b010],
+        'K' => [0b101, 0b101, 0b110, 0b101, 0b101],
+        'L' => [0b100, 0b100, 0b100, 0b100, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'N' => [0b110, 0b101, 0b101, 0b101, 0b101],
+        'O' => [0b010, 0b101, 0b101, 0b101, 0b010],
+        'P' => [0b110, 0b101, 0b110, 0b100, 0b100],
+        'Q' => [0b010, 0b101, 0b101, 0b110, 0b011],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b011, 0b100, 0b010, 0b001, 0b110],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        'V' => [0b101, 0b101, 0b101, 0b101, 0b010],
+        'W' => [0b101, 0b101, 0b111, 0b111, 0b101],
+        'X' => [0b101, 0b101, 0b010, 0b101, 0b101],
+        'Y' => [0b101, 0b101, 0b010, 0b010, 0b010],
+        'Z' => [0b111, 0b001, 0b010, 0b100, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '/' => [0b001, 0b001, 0b010, 0b100, 0b100],
+        ' ' => [0b000; 5],
+        _ => [0b111, 0b001, 0b011, 0b000, 0b010],
+    }
+}
+
+#[cfg(feature = "gui")]
+impl Renderer for Canvas<Window> {
+    fn clear(&mut self, color: Color) {
+        self.set_draw_color(sdl2::pixels::Color::RGB(color.r, color.g, color.b));
+        Canvas::clear(self);
+    }
+
+    fn fill_rect(&mut self, x: i32, y: i32, width: u32, height: u32, color: Color) {
+        self.set_draw_color(sdl2::pixels::Color::RGB(color.r, color.g, color.b));
+        Canvas::fill_rect(self, Rect::new(x, y, width, height)).unwrap();
+    }
+
+    fn draw_line(&mut self, start: (i32, i32), end: (i32, i32), color: Color) {
+        self.set_draw_color(sdl2::pixels::Color::RGB(color.r, color.g, color.b));
+        Canvas::draw_line(self, Point::from(start), Point::from(end)).unwrap();
+    }
+
+    fn present(&mut self) {
+        Canvas::present(self);
+    }
+}
+
+/// An in-memory RGB image that can be drawn to without a display, e.g. on a
+/// headless server or to check what was drawn.
+#[derive(Clone, Debug)]
+pub struct Framebuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<Color>, // laid out as pixels[y][x]
+    frames_presented: usize,
+}
+
+impl Framebuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![Color::BLACK; (width as usize) * (height as usize)],
+            frames_presented: 0,
+        }
+    }
+
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn get_pixel(&self, x: u32, y: u32) -> Color {
+        if x >= self.width || y >= self.height {
+            panic!(
+                "Pixel ({}, {}) is outside of {}x{} framebuffer",
+                x, y, self.width, self.height
+            );
+        }
+
+        self.pixels[(y as usize) * (self.width as usize) + (x as usize)]
+    }
+
+    pub fn get_frames_presented(&self) -> usize {
+        self.frames_presented
+    }
+
+    /// Pixel data as packed RGB bytes, row by row.
+    pub fn to_rgb_bytes(&self) -> Vec<u8> {
+        self.pixels
+            .iter()
+            .flat_map(|color| [color.r, color.g, color.b])
+            .collect()
+    }
+
+    // Anything outside the buffer is silently clipped, the same as SDL does.
+    fn set_pixel(&mut self, x: i32, y: i32, color: Color) {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return;
+        }
+
+        self.pixels[(y as usize) * (self.width as usize) + (x as usize)] = color;
+    }
+}
+
+impl Renderer for Framebuffer {
+    fn clear(&mut self, color: Color) {
+        self.pixels.iter_mut().for_each(|pixel| *pixel = color);
+    }
+
+    fn fill_rect(&mut self, x: i32, y: i32, width: u32, height: u32, color: Color) {
+        for pixel_y in y..(y + height as i32) {
+            for pixel_x in x..(x + width as i32) {
+                self.set_pixel(pixel_x, pixel_y, color);
+            }
+        }
+    }
+
+    fn draw_line(&mut self, start: (i32, i32), end: (i32, i32), color: Color) {
+        // Bresenham's line algorithm.
+        let (mut x, mut y) = start;
+        let dx = (end.0 - x).abs();
+        let dy = -(end.1 - y).abs();
+        let step_x = if x < end.0 { 1 } else { -1 };
+        let step_y = if y < end.1 { 1 } else { -1 };
+        let mut error = dx + dy;
+
+        loop {
+            self.set_pixel(x, y, color);
+            if (x, y) == end {
+                break;
+            }
+
+            let doubled_error = 2 * error;
+            if doubled_error >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled_error <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    fn present(&mut self) {
+        self.frames_presented += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::snake::{Direction, FoodPlacement, Game, GameConfig, Location, Spawn};
+
+    const RED: Color = Color::rgb(255, 0, 0);
+
+    fn lit_pixels(framebuffer: &Framebuffer) -> Vec<(u32, u32)> {
+        let mut lit = Vec::new();
+        for y in 0..framebuffer.get_height() {
+            for x in 0..framebuffer.get_width() {
+                if framebuffer.get_pixel(x, y) != Color::BLACK {
+                    lit.push((x, y));
+                }
+            }
+        }
+
+        lit
+    }
+
+    #[test]
+    fn fill_rect_clips_to_the_buffer() {
+        let mut framebuffer = Framebuffer::new(8, 6);
+        framebuffer.fill_rect(-2, -3, 4, 5, RED);
+        framebuffer.fill_rect(6, 4, 10, 10, RED);
+
+        let mut expected = Vec::new();
+        for y in 0..2 {
+            for x in 0..2 {
+                expected.push((x, y));
+            }
+        }
+        for y in 4..6 {
+            for x in 6..8 {
+                expected.push((x, y));
+            }
+        }
+        expected.sort_by_key(|&(x, y)| (y, x));
+        assert_eq!(lit_pixels(&framebuffer), expected);
+
+        // Entirely off the buffer draws nothing.
+        let mut framebuffer = Framebuffer::new(8, 6);
+        framebuffer.fill_rect(-5, 0, 5, 6, RED);
+        framebuffer.fill_rect(0, 6, 8, 3, RED);
+        assert!(lit_pixels(&framebuffer).is_empty());
+    }
+
+    #[test]
+    fn lines_reach_both_endpoints_in_every_octant() {
+        let start = (10, 10);
+        let ends = [
+            (15, 12),
+            (12, 15),
+            (8, 15),
+            (5, 12),
+            (5, 8),
+            (8, 5),
+            (12, 5),
+            (15, 8),
+        ];
+
+        for &end in ends.iter() {
+            let mut framebuffer = Framebuffer::new(20, 20);
+            framebuffer.draw_line(start, end, RED);
+
+            assert_eq!(framebuffer.get_pixel(10, 10), RED, "start of {:?}", end);
+            assert_eq!(
+                framebuffer.get_pixel(end.0 as u32, end.1 as u32),
+                RED,
+                "end of {:?}",
+                end
+            );
+
+            // One pixel per step along the longer axis.
+            let longest = i32::max((end.0 - start.0).abs(), (end.1 - start.1).abs());
+            assert_eq!(lit_pixels(&framebuffer).len(), longest as usize + 1);
+        }
+    }
+
+    #[test]
+    fn game_draws_snake_and_food_tiles() {
+        let config = GameConfig {
+            width: 6,
+            height: 4,
+            initial_length: 3,
+            spawn: Spawn::Fixed {
+                head: Location::new(2, 1),
+                direction: Direction::Right,
+            },
+            first_food: FoodPlacement::Fixed(Location::new(4, 3)),
+            growth_per_food: 1,
+        };
+        let tile = 3;
+        let mut framebuffer = Framebuffer::new(6 * tile, 4 * tile);
+        Game::new(&config, 0).render(&mut framebuffer, tile as u16);
+
+        let background = Color::rgb(127, 127, 127);
+        let snake = Color::rgb(255, 0, 0);
+        let food = Color::rgb(0, 255, 0);
+        for tile_y in 0..4 {
+            for tile_x in 0..6 {
+                let expected = match (tile_x, tile_y) {
+                    (0..=2, 1) => snake,
+                    (4, 3) => food,
+                    _ => background,
+                };
+
+                // Every pixel of the tile, so tiles are the right size too.
+                for y in 0..tile {
+                    for x in 0..tile {
+                        assert_eq!(
+                            framebuffer.get_pixel(tile_x * tile + x, tile_y * tile + y),
+                            expected,
+                            "tile ({}, {})",
+                            tile_x,
+                            tile_y
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
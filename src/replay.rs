@@ -0,0 +1,229 @@
+use crate::format::{self, FormatError};
+use crate::snake::{Direction, Game, GameConfig, GameStatus};
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+/// A record of a single game: everything needed to play it again exactly.
+/// Games are deterministic given their config and seed, so the only other
+/// thing to store is the direction the snake was heading on each step.
+#[derive(Clone, Debug)]
+pub struct Replay {
+    config: GameConfig,
+    seed: u64,
+    inputs: Vec<Direction>,
+}
+
+impl Replay {
+    const MAGIC: &'static [u8; 4] = b"SRPL";
+    const FORMAT_VERSION: u32 = 1;
+
+    pub fn new(config: GameConfig, seed: u64) -> Self {
+        Self {
+            config,
+            seed,
+            inputs: Vec::new(),
+        }
+    }
+
+    pub(crate) fn push(&mut self, direction: Direction) {
+        self.inputs.push(direction);
+    }
+
+    pub fn get_config(&self) -> &GameConfig {
+        &self.config
+    }
+
+    pub fn get_seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn get_inputs(&self) -> &[Direction] {
+        &self.inputs
+    }
+
+    /// Starts playing the game back from the beginning.
+    pub fn play(&self) -> ReplayPlayer<'_> {
+        ReplayPlayer {
+            replay: self,
+            game: Game::new(&self.config, self.seed),
+            next_input: 0,
+        }
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), FormatError> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        self.write_to(&mut writer)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, FormatError> {
+        Self::read_from(&mut BufReader::new(File::open(path)?))
+    }
+
+    /// Writes the replay in the following format, all little-endian:
+    ///
+    /// ```text
+    /// magic        b"SRPL"
+    /// version      u32, currently 1
+    /// seed         u64
+    /// game config  see `GameConfig::write_to`
+    /// input count  u64
+    /// inputs       two bits per direction, four to a byte, first in the low bits
+    /// ```
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        format::write_header(writer, Self::MAGIC, Self::FORMAT_VERSION)?;
+        format::write_u64(writer, self.seed)?;
+        self.config.write_to(writer)?;
+
+        format::write_u64(writer, self.inputs.len() as u64)?;
+        for chunk in self.inputs.chunks(4) {
+            let packed = chunk.iter().enumerate().fold(0, |byte, (i, direction)| {
+                byte | (direction.to_code() << (2 * i))
+            });
+            format::write_u8(writer, packed)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn read_from(reader: &mut impl Read) -> Result<Self, FormatError> {
+        format::read_header(reader, Self::MAGIC, Self::FORMAT_VERSION)?;
+        let seed = format::read_u64(reader)?;
+        let config = GameConfig::read_from(reader)?;
+
+        let input_count = format::read_usize(reader)?;
+        let mut inputs = Vec::new();
+        while inputs.len() < input_count {
+            let packed = format::read_u8(reader)?;
+            for i in 0..usize::min(4, input_count - inputs.len()) {
+                inputs.push(Direction::from_code((packed >> (2 * i)) & 0b11)?);
+            }
+        }
+
+        Ok(Self {
+            config,
+            seed,
+            inputs,
+        })
+    }
+}
+
+/// Steps through a replay one frame at a time.
+pub struct ReplayPlayer<'a> {
+    replay: &'a Replay,
+    game: Game,
+    next_input: usize,
+}
+
+impl<'a> ReplayPlayer<'a> {
+    /// Advances the game by one recorded step, or returns `None` once every
+    /// recorded step has been played.
+    pub fn step(&mut self) -> Option<GameStatus> {
+        let direction = *self.replay.inputs.get(self.next_input)?;
+        self.next_input += 1;
+
+        self.game.turn_snake(direction);
+        Some(self.game.step())
+    }
+
+    /// The game as of the last step played.
+    pub fn get_game(&self) -> &Game {
+        &self.game
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::snake::{FoodPlacement, Location, Spawn};
+
+    // Plays a game with the snake circling a square, for at most
+    // `max_steps` or until it's over. Returns its replay and what every step
+    // returned.
+    fn record_game(config: &GameConfig, seed: u64, max_steps: usize) -> (Replay, Vec<String>) {
+        let sides = [
+            Direction::Right,
+            Direction::Down,
+            Direction::Left,
+            Direction::Up,
+        ];
+        let mut game = Game::new_recorded(config, seed);
+        let mut statuses = Vec::new();
+
+        for step in 0..max_steps {
+            game.turn_snake(sides[(step / 4) % 4]);
+            let status = game.step();
+            statuses.push(format!("{:?} {:?}", status, game.get_snake_head_location()));
+            if let GameStatus::Over = status {
+                break;
+            }
+        }
+
+        (game.take_replay().unwrap(), statuses)
+    }
+
+    #[test]
+    fn replays_play_back_the_recorded_game() {
+        // Food is placed at random, so the seed decides what the snake eats
+        // along the way.
+        let config = GameConfig {
+            spawn: Spawn::Fixed {
+                head: Location::new(2, 1),
+                direction: Direction::Right,
+            },
+            first_food: FoodPlacement::Random,
+            ..GameConfig::sized(8, 8)
+        };
+        for seed in 0..5 {
+            let (replay, statuses) = record_game(&config, seed, 100);
+            assert_eq!(replay.get_inputs().len(), statuses.len());
+
+            let mut player = replay.play();
+            for status in statuses.iter() {
+                let played = player.step().unwrap();
+                let head = player.get_game().get_snake_head_location();
+                assert_eq!(&format!("{:?} {:?}", played, head), status);
+            }
+            assert!(player.step().is_none());
+        }
+    }
+
+    #[test]
+    fn replays_round_trip() {
+        let config = GameConfig::sized(8, 6);
+        let mut replay = Replay::new(config, 42);
+        let mut empty_bytes = Vec::new();
+        replay.write_to(&mut empty_bytes).unwrap();
+
+        // Every direction, with a count that leaves the last byte part full.
+        let inputs = [
+            Direction::Up,
+            Direction::Down,
+            Direction::Left,
+            Direction::Right,
+            Direction::Right,
+            Direction::Left,
+            Direction::Up,
+        ];
+        for &direction in inputs.iter() {
+            replay.push(direction);
+        }
+
+        let mut bytes = Vec::new();
+        replay.write_to(&mut bytes).unwrap();
+        assert_eq!(bytes.len() - empty_bytes.len(), 2, "four inputs to a byte");
+
+        let read = Replay::read_from(&mut bytes.as_slice()).unwrap();
+        assert_eq!(read.get_seed(), 42);
+        assert_eq!(read.get_inputs(), &inputs[..]);
+        assert_eq!(
+            format!("{:?}", read.get_config()),
+            format!("{:?}", replay.get_config())
+        );
+    }
+}
@@ -0,0 +1,46 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use crate::format;
+
+use std::io::{self, Read, Write};
+
+/// The random number generator used for everything in a simulation. Seeding
+/// it explicitly is what makes a training run reproducible.
+pub type SimRng = ChaCha8Rng;
+
+pub fn from_seed(seed: u64) -> SimRng {
+    SimRng::seed_from_u64(seed)
+}
+
+/// An independent stream derived from `seed`. Work that gets split up over
+/// rayon's thread pool takes one stream per item, so results don't depend on
+/// which thread ends up running what.
+pub fn sub_stream(seed: u64, stream: u64) -> SimRng {
+    let mut rng = from_seed(seed);
+    rng.set_stream(stream);
+
+    rng
+}
+
+/// Writes everything needed to pick `rng` back up where it left off: its
+/// 32 byte seed, its stream (`u64`) and its position in that stream (`u128`).
+pub fn write_state(rng: &SimRng, writer: &mut impl Write) -> io::Result<()> {
+    writer.write_all(&rng.get_seed())?;
+    format::write_u64(writer, rng.get_stream())?;
+    writer.write_all(&rng.get_word_pos().to_le_bytes())
+}
+
+pub fn read_state(reader: &mut impl Read) -> io::Result<SimRng> {
+    let mut seed = [0; 32];
+    reader.read_exact(&mut seed)?;
+    let stream = format::read_u64(reader)?;
+    let mut word_pos = [0; 16];
+    reader.read_exact(&mut word_pos)?;
+
+    let mut rng = SimRng::from_seed(seed);
+    rng.set_stream(stream);
+    rng.set_word_pos(u128::from_le_bytes(word_pos));
+
+    Ok(rng)
+}
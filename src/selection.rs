@@ -0,0 +1,321 @@
+use crate::format::{self, FormatError};
+use crate::rng::SimRng;
+
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::str::FromStr;
+
+/// How parents are picked from an evaluated population.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SelectionStrategy {
+    /// Uniformly from the fittest `fraction` of the population.
+    Truncation { fraction: f64 },
+    /// The fittest of `size` agents drawn at random.
+    Tournament { size: usize },
+    /// With probability proportional to fitness. Fitness is shifted so the
+    /// least fit agent has none, which keeps negative fitness workable.
+    Roulette,
+    /// With probability proportional to rank, from 1 for the least fit to
+    /// the population size for the fittest.
+    Rank,
+    /// Like `Roulette`, but every parent comes from a single spin with
+    /// evenly spaced pointers, so the picks can't all bunch up by chance.
+    StochasticUniversal,
+}
+
+impl Default for SelectionStrategy {
+    fn default() -> Self {
+        SelectionStrategy::Truncation { fraction: 0.1 }
+    }
+}
+
+impl SelectionStrategy {
+    /// Picks `count` parents, returning their indices into `fitness`. The
+    /// same agent can be picked more than once.
+    pub fn select(&self, fitness: &[f64], count: usize, rng: &mut SimRng) -> Vec<usize> {
+        if fitness.is_empty() {
+            panic!("Cannot select from an empty population");
+        }
+
+        if let Err(err) = self.validate() {
+            panic!("{}", err);
+        }
+
+        match *self {
+            SelectionStrategy::Truncation { fraction } => {
+                let ranked = rank_order(fitness);
+                let num_top_agents = ((fraction * (fitness.len() as f64)) as usize).max(1);
+                let top_agents = &ranked[(ranked.len() - num_top_agents)..];
+
+                (0..count)
+                    .map(|_| *top_agents.choose(rng).unwrap())
+                    .collect()
+            }
+            SelectionStrategy::Tournament { size } => (0..count)
+                .map(|_| {
+                    (0..size)
+                        .map(|_| rng.gen_range(0..fitness.len()))
+                        .max_by(|&i, &j| fitness[i].partial_cmp(&fitness[j]).unwrap())
+                        .unwrap()
+                })
+                .collect(),
+            SelectionStrategy::Roulette => {
+                let cumulative = cumulative_weights(&shifted_fitness(fitness));
+                (0..count)
+                    .map(|_| spin(&cumulative, rng.gen::<f64>()))
+                    .collect()
+            }
+            SelectionStrategy::Rank => {
+                let mut weights = vec![0.0; fitness.len()];
+                for (rank, index) in rank_order(fitness).into_iter().enumerate() {
+                    weights[index] = (rank + 1) as f64;
+                }
+
+                let cumulative = cumulative_weights(&weights);
+                (0..count)
+                    .map(|_| spin(&cumulative, rng.gen::<f64>()))
+                    .collect()
+            }
+            SelectionStrategy::StochasticUniversal => {
+                let cumulative = cumulative_weights(&shifted_fitness(fitness));
+                let spacing = 1.0 / (count as f64);
+                let start = rng.gen::<f64>() * spacing;
+
+                // The pointers pick parents in population order, so shuffle
+                // them to keep neighbours from always being paired up.
+                let mut parents = (0..count)
+                    .map(|i| spin(&cumulative, start + (i as f64) * spacing))
+                    .collect::<Vec<_>>();
+                parents.shuffle(rng);
+
+                parents
+            }
+        }
+    }
+
+    /// Checks the strategy's parameters: a truncation fraction must be in
+    /// (0, 1] and a tournament needs at least one agent.
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            SelectionStrategy::Truncation { fraction } if !(fraction > 0.0 && fraction <= 1.0) => {
+                Err(format!(
+                    "Truncation fraction must be in (0, 1], got {}",
+                    fraction
+                ))
+            }
+            SelectionStrategy::Tournament { size: 0 } => {
+                Err("Tournament size must be at least 1".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        match *self {
+            SelectionStrategy::Truncation { fraction } => {
+                format::write_u8(writer, 0)?;
+                format::write_f64(writer, fraction)
+            }
+            SelectionStrategy::Tournament { size } => {
+                format::write_u8(writer, 1)?;
+                format::write_u64(writer, size as u64)
+            }
+            SelectionStrategy::Roulette => format::write_u8(writer, 2),
+            SelectionStrategy::Rank => format::write_u8(writer, 3),
+            SelectionStrategy::StochasticUniversal => format::write_u8(writer, 4),
+        }
+    }
+
+    /// Reads a strategy written by `write_to`, checking its parameters, see
+    /// `validate`.
+    pub(crate) fn read_from(reader: &mut impl Read) -> Result<Self, FormatError> {
+        let strategy = match format::read_u8(reader)? {
+            0 => SelectionStrategy::Truncation {
+                fraction: format::read_f64(reader)?,
+            },
+            1 => SelectionStrategy::Tournament {
+                size: format::read_usize(reader)?,
+            },
+            2 => SelectionStrategy::Roulette,
+            3 => SelectionStrategy::Rank,
+            4 => SelectionStrategy::StochasticUniversal,
+            other => {
+                return Err(FormatError::Invalid(format!(
+                    "{} is not a valid selection strategy",
+                    other
+                )))
+            }
+        };
+        strategy.validate().map_err(FormatError::Invalid)?;
+
+        Ok(strategy)
+    }
+}
+
+/// Written the same way it's parsed, e.g. `truncation:0.1` or `tournament:3`.
+impl fmt::Display for SelectionStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SelectionStrategy::Truncation { fraction } => write!(f, "truncation:{}", fraction),
+            SelectionStrategy::Tournament { size } => write!(f, "tournament:{}", size),
+            SelectionStrategy::Roulette => write!(f, "roulette"),
+            SelectionStrategy::Rank => write!(f, "rank"),
+            SelectionStrategy::StochasticUniversal => write!(f, "sus"),
+        }
+    }
+}
+
+impl FromStr for SelectionStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, param) = match s.split_once(':') {
+            Some((name, param)) => (name, Some(param)),
+            None => (s, None),
+        };
+
+        match (name, param) {
+            ("truncation", None) => Ok(SelectionStrategy::default()),
+            ("truncation", Some(fraction)) => match fraction.parse() {
+                Ok(fraction) if fraction > 0.0 && fraction <= 1.0 => {
+                    Ok(SelectionStrategy::Truncation { fraction })
+                }
+                _ => Err(format!(
+                    "Truncation fraction must be in (0, 1], got '{}'",
+                    fraction
+                )),
+            },
+            ("tournament", Some(size)) => match size.parse() {
+                Ok(size) if size > 0 => Ok(SelectionStrategy::Tournament { size }),
+                _ => Err(format!(
+                    "Tournament size must be at least 1, got '{}'",
+                    size
+                )),
+            },
+            ("tournament", None) => Err("Tournament needs a size, e.g. tournament:3".to_string()),
+            ("roulette", None) => Ok(SelectionStrategy::Roulette),
+            ("rank", None) => Ok(SelectionStrategy::Rank),
+            ("sus", None) => Ok(SelectionStrategy::StochasticUniversal),
+            _ => Err(format!("Unknown selection strategy '{}'", s)),
+        }
+    }
+}
+
+// Indices into `fitness`, least fit first. Ties stay in population order,
+// which keeps selection deterministic.
+fn rank_order(fitness: &[f64]) -> Vec<usize> {
+    let mut order = (0..fitness.len()).collect::<Vec<_>>();
+    order.sort_by(|&i, &j| fitness[i].partial_cmp(&fitness[j]).unwrap());
+
+    order
+}
+
+// Fitness shifted so the least fit agent has a weight of zero.
+fn shifted_fitness(fitness: &[f64]) -> Vec<f64> {
+    let min = fitness.iter().copied().fold(f64::INFINITY, f64::min);
+
+    fitness.iter().map(|&val| val - min).collect()
+}
+
+// Running totals of `weights`, normalised so the last is 1. If every weight
+// is zero, every agent is equally likely instead.
+fn cumulative_weights(weights: &[f64]) -> Vec<f64> {
+    let total: f64 = weights.iter().sum();
+    let uniform = 1.0 / (weights.len() as f64);
+
+    weights
+        .iter()
+        .scan(0.0, |sum, &weight| {
+            *sum += if total > 0.0 { weight / total } else { uniform };
+            Some(*sum)
+        })
+        .collect()
+}
+
+// The index whose slice of the wheel `pointer`, in [0, 1), lands in.
+fn spin(cumulative: &[f64], pointer: f64) -> usize {
+    let index = cumulative.partition_point(|&edge| edge <= pointer);
+
+    // Rounding can leave the last edge just short of 1.
+    usize::min(index, cumulative.len() - 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::rng;
+
+    #[test]
+    fn tournaments_favour_the_fittest() {
+        let fitness = (0..10).map(f64::from).collect::<Vec<f64>>();
+        let picks = SelectionStrategy::Tournament { size: 3 }.select(
+            &fitness,
+            1000,
+            &mut rng::from_seed(0),
+        );
+
+        // A tournament of 3 picks the fittest with a chance of
+        // 1 - 0.9^3 = 27%, against 10% for a uniform pick.
+        let fittest = picks.iter().filter(|&&pick| pick == 9).count();
+        assert!(fittest > 200, "{}", fittest);
+        assert!(picks.iter().all(|&pick| pick < fitness.len()));
+    }
+
+    #[test]
+    fn stochastic_universal_picks_are_evenly_spaced() {
+        // Shifted to weights of 0, 1, 2, 3 and 4, so every agent but the
+        // least fit owns a whole number of the 10 evenly spaced pointers.
+        let fitness = [0.0, 1.0, 2.0, 3.0, 4.0];
+        for seed in 0..10 {
+            let picks = SelectionStrategy::StochasticUniversal.select(
+                &fitness,
+                10,
+                &mut rng::from_seed(seed),
+            );
+
+            let mut counts = [0; 5];
+            for pick in picks {
+                counts[pick] += 1;
+            }
+            assert_eq!(counts, [0, 1, 2, 3, 4]);
+        }
+    }
+
+    #[test]
+    fn equal_or_zero_fitness_is_handled() {
+        for fitness in [[0.0; 4], [3.0; 4]].iter() {
+            // With no differences in fitness, the wheel falls back to every
+            // agent being equally likely.
+            for strategy in [
+                SelectionStrategy::Roulette,
+                SelectionStrategy::StochasticUniversal,
+            ]
+            .iter()
+            {
+                let mut counts = [0; 4];
+                for pick in strategy.select(fitness, 400, &mut rng::from_seed(1)) {
+                    counts[pick] += 1;
+                }
+                assert!(
+                    counts.iter().all(|&count| count > 50),
+                    "{} picked {:?} from {:?}",
+                    strategy,
+                    counts,
+                    fitness
+                );
+            }
+
+            // Ties are ranked in population order, so every agent still has
+            // a rank, and a chance of being picked.
+            let mut counts = [0; 4];
+            for pick in SelectionStrategy::Rank.select(fitness, 400, &mut rng::from_seed(1)) {
+                counts[pick] += 1;
+            }
+            assert!(counts.iter().all(|&count| count > 0), "{:?}", counts);
+        }
+    }
+}
@@ -1,13 +1,15 @@
 use rand::Rng;
 
-use sdl2::pixels::Color;
-use sdl2::rect::{Point, Rect};
-use sdl2::render::Canvas;
-use sdl2::video::Window;
+use crate::format::{self, FormatError};
+use crate::replay::Replay;
+use crate::rng::{self, SimRng};
+
+use crate::render::{Color, Renderer};
 
 use std::convert::{TryFrom, TryInto};
+use std::io::{self, Read, Write};
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum Direction {
     Up,
     Down,
@@ -39,9 +41,23 @@ pub struct Location {
 }
 
 impl Location {
-    fn random_location(width: u16, height: u16) -> Self {
-        let mut rng = rand::thread_rng();
+    pub fn new(x: i16, y: i16) -> Self {
+        Self { x, y }
+    }
+
+    fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        format::write_i16(writer, self.x)?;
+        format::write_i16(writer, self.y)
+    }
 
+    fn read_from(reader: &mut impl Read) -> Result<Self, FormatError> {
+        Ok(Self {
+            x: format::read_i16(reader)?,
+            y: format::read_i16(reader)?,
+        })
+    }
+
+    fn random_location(width: u16, height: u16, rng: &mut SimRng) -> Self {
         Self {
             x: rng.gen_range(0..width).try_into().unwrap(),
             y: rng.gen_range(0..height).try_into().unwrap(),
@@ -49,47 +65,403 @@ impl Location {
     }
 }
 
+impl Direction {
+    const ALL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Down,
+        Direction::Left,
+        Direction::Right,
+    ];
+
+    fn random_direction(rng: &mut SimRng) -> Self {
+        Self::ALL[rng.gen_range(0..Self::ALL.len())]
+    }
+
+    pub(crate) fn to_code(self) -> u8 {
+        match self {
+            Direction::Up => 0,
+            Direction::Down => 1,
+            Direction::Left => 2,
+            Direction::Right => 3,
+        }
+    }
+
+    pub(crate) fn from_code(code: u8) -> Result<Self, FormatError> {
+        match code {
+            0 => Ok(Direction::Up),
+            1 => Ok(Direction::Down),
+            2 => Ok(Direction::Left),
+            3 => Ok(Direction::Right),
+            other => Err(FormatError::Invalid(format!(
+                "{} is not a valid direction",
+                other
+            ))),
+        }
+    }
+
+    // Offset of one step in this direction, with y growing downwards.
+    fn offset(self) -> (i16, i16) {
+        match self {
+            Direction::Up => (0, -1),
+            Direction::Down => (0, 1),
+            Direction::Left => (-1, 0),
+            Direction::Right => (1, 0),
+        }
+    }
+}
+
+/// Where the snake starts out, and which way it is heading.
+#[derive(Clone, Copy, Debug)]
+pub enum Spawn {
+    /// Head placed at `head`, with the body trailing straight out behind it.
+    Fixed {
+        head: Location,
+        direction: Direction,
+    },
+    /// Random heading, and a random head position that fits the whole body on the board.
+    Random,
+}
+
+/// Where the first piece of food is placed. Every piece after the first is random.
+#[derive(Clone, Copy, Debug)]
+pub enum FoodPlacement {
+    Fixed(Location),
+    Random,
+}
+
+/// Everything needed to set up a new `Game`.
+#[derive(Clone, Copy, Debug)]
+pub struct GameConfig {
+    pub width: u16,
+    pub height: u16,
+    pub initial_length: u16,
+    pub spawn: Spawn,
+    pub first_food: FoodPlacement,
+    pub growth_per_food: u16,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        Self {
+            width: 50,
+            height: 50,
+            initial_length: 3,
+            spawn: Spawn::Fixed {
+                head: Location { x: 2, y: 0 },
+                direction: Direction::Right,
+            },
+            first_food: FoodPlacement::Fixed(Location { x: 10, y: 15 }),
+            growth_per_food: 1,
+        }
+    }
+}
+
+impl GameConfig {
+    /// Writes the config as its sizes (`u16`s), then the spawn and first food
+    /// placements, each as a tag byte followed by any location (`i16`s) and
+    /// direction (a byte).
+    pub fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        format::write_u16(writer, self.width)?;
+        format::write_u16(writer, self.height)?;
+        format::write_u16(writer, self.initial_length)?;
+        format::write_u16(writer, self.growth_per_food)?;
+
+        match self.spawn {
+            Spawn::Fixed { head, direction } => {
+                format::write_u8(writer, 0)?;
+                head.write_to(writer)?;
+                format::write_u8(writer, direction.to_code())?;
+            }
+            Spawn::Random => format::write_u8(writer, 1)?,
+        }
+
+        match self.first_food {
+            FoodPlacement::Fixed(location) => {
+                format::write_u8(writer, 0)?;
+                location.write_to(writer)?;
+            }
+            FoodPlacement::Random => format::write_u8(writer, 1)?,
+        }
+
+        Ok(())
+    }
+
+    /// Reads a config written by `write_to`, checking it's one a game can
+    /// actually be started with, see `validate`.
+    pub fn read_from(reader: &mut impl Read) -> Result<Self, FormatError> {
+        let width = format::read_u16(reader)?;
+        let height = format::read_u16(reader)?;
+        let initial_length = format::read_u16(reader)?;
+        let growth_per_food = format::read_u16(reader)?;
+
+        let spawn = match format::read_u8(reader)? {
+            0 => Spawn::Fixed {
+                head: Location::read_from(reader)?,
+                direction: Direction::from_code(format::read_u8(reader)?)?,
+            },
+            1 => Spawn::Random,
+            other => {
+                return Err(FormatError::Invalid(format!(
+                    "{} is not a valid spawn",
+                    other
+                )))
+            }
+        };
+
+        let first_food = match format::read_u8(reader)? {
+            0 => FoodPlacement::Fixed(Location::read_from(reader)?),
+            1 => FoodPlacement::Random,
+            other => {
+                return Err(FormatError::Invalid(format!(
+                    "{} is not a valid food placement",
+                    other
+                )))
+            }
+        };
+
+        let config = Self {
+            width,
+            height,
+            initial_length,
+            spawn,
+            first_food,
+            growth_per_food,
+        };
+        config.validate().map_err(FormatError::Invalid)?;
+
+        Ok(config)
+    }
+
+    /// The default config on a board of a different size. If the default
+    /// first food doesn't fit on the board, it's placed randomly instead.
+    pub fn sized(width: u16, height: u16) -> Self {
+        let mut config = Self {
+            width,
+            height,
+            ..Self::default()
+        };
+
+        if let FoodPlacement::Fixed(location) = config.first_food {
+            if !config.on_board(location) {
+                config.first_food = FoodPlacement::Random;
+            }
+        }
+
+        config
+    }
+
+    /// Checks that a game can be started with this config: the board fits
+    /// in `i16` coordinates, the snake fits on it wherever it may spawn with
+    /// room left over for food, and any fixed first food is on the board.
+    pub fn validate(&self) -> Result<(), String> {
+        let max_size = i16::MAX as u16;
+        if self.width == 0 || self.height == 0 || self.width > max_size || self.height > max_size {
+            return Err(format!(
+                "A {}x{} board is not allowed, both sides must be from 1 to {}",
+                self.width, self.height, max_size
+            ));
+        }
+
+        if self.initial_length == 0 {
+            return Err("The snake must start with a length of at least 1".to_string());
+        }
+
+        if u32::from(self.initial_length) >= u32::from(self.width) * u32::from(self.height) {
+            return Err(format!(
+                "A snake of length {} leaves no room for food on a {}x{} board",
+                self.initial_length, self.width, self.height
+            ));
+        }
+
+        match self.spawn {
+            Spawn::Fixed { head, direction } => {
+                // The tail is the furthest segment from the head, so if both
+                // ends are on the board, so is everything between.
+                let (dx, dy) = direction.offset();
+                let back = i32::from(self.initial_length) - 1;
+                let tail_x = i32::from(head.x) - i32::from(dx) * back;
+                let tail_y = i32::from(head.y) - i32::from(dy) * back;
+                let tail_on_board = tail_x >= 0
+                    && tail_y >= 0
+                    && tail_x < i32::from(self.width)
+                    && tail_y < i32::from(self.height);
+
+                if !self.on_board(head) || !tail_on_board {
+                    return Err(format!(
+                        "Snake of length {} at {:?} heading {:?} does not fit on a {}x{} board",
+                        self.initial_length, head, direction, self.width, self.height
+                    ));
+                }
+            }
+            Spawn::Random => {
+                // Any heading can be picked, so the snake has to fit both
+                // across and down the board.
+                if self.initial_length > u16::min(self.width, self.height) {
+                    return Err(format!(
+                        "Snake of length {} does not fit every way round a {}x{} board",
+                        self.initial_length, self.width, self.height
+                    ));
+                }
+            }
+        }
+
+        if let FoodPlacement::Fixed(location) = self.first_food {
+            if !self.on_board(location) {
+                return Err(format!(
+                    "First food at {:?} is off the {}x{} board",
+                    location, self.width, self.height
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn on_board(&self, location: Location) -> bool {
+        location.x >= 0
+            && location.y >= 0
+            && i32::from(location.x) < i32::from(self.width)
+            && i32::from(location.y) < i32::from(self.height)
+    }
+
+    fn spawn_snake(&self, rng: &mut SimRng) -> (Vec<Location>, Direction) {
+        let length = i16::try_from(self.initial_length).unwrap();
+        let width = i16::try_from(self.width).unwrap();
+        let height = i16::try_from(self.height).unwrap();
+
+        let (head, direction) = match self.spawn {
+            Spawn::Fixed { head, direction } => (head, direction),
+            Spawn::Random => {
+                let direction = Direction::random_direction(rng);
+                let (dx, dy) = direction.offset();
+
+                // The tail trails behind the head, so leave room for it on that side.
+                let (min_x, max_x) = match dx {
+                    1 => (length - 1, width - 1),
+                    -1 => (0, width - length),
+                    _ => (0, width - 1),
+                };
+                let (min_y, max_y) = match dy {
+                    1 => (length - 1, height - 1),
+                    -1 => (0, height - length),
+                    _ => (0, height - 1),
+                };
+
+                let head = Location {
+                    x: rng.gen_range(min_x..=max_x),
+                    y: rng.gen_range(min_y..=max_y),
+                };
+
+                (head, direction)
+            }
+        };
+
+        let (dx, dy) = direction.offset();
+        let snake = (0..length)
+            .rev()
+            .map(|i| Location {
+                x: head.x - (dx * i),
+                y: head.y - (dy * i),
+            })
+            .collect::<Vec<_>>();
+
+        (snake, direction)
+    }
+}
+
 pub struct Game {
     width: u16,
     height: u16,
-    tile_size: u16,
     snake: Vec<Location>, // the "front" of the snake is stored at the end
     snake_direction: Direction,
     food_loc: Location,
+    growth_per_food: u16,
     game_in_progress: bool,
+    rng: SimRng,
+    recording: Option<Replay>,
 }
 
 impl Game {
-    const BACKGROUND_COLOR: Color = Color::RGB(127, 127, 127);
-    const SNAKE_COLOR: Color = Color::RGB(255, 0, 0);
-    const FOOD_COLOR: Color = Color::RGB(0, 255, 0);
-    const LINE_COLOR: Color = Color::RGB(0, 0, 255);
+    const BACKGROUND_COLOR: Color = Color::rgb(127, 127, 127);
+    const SNAKE_COLOR: Color = Color::rgb(255, 0, 0);
+    const FOOD_COLOR: Color = Color::rgb(0, 255, 0);
+
+    /// Starts a new game. Everything random about it (spawn, food) is drawn
+    /// from `seed`, so two games with the same config and seed that are given
+    /// the same inputs play out identically. Panics if `config` isn't
+    /// valid, see `GameConfig::validate`.
+    pub fn new(config: &GameConfig, seed: u64) -> Self {
+        if let Err(err) = config.validate() {
+            panic!("Cannot start a game: {}", err);
+        }
 
-    pub fn new(width: u16, height: u16, tile_size: u16) -> Self {
-        let mut snake = Vec::new();
+        let mut rng = rng::from_seed(seed);
+        let (snake, snake_direction) = config.spawn_snake(&mut rng);
 
-        snake.push(Location { x: 0, y: 0 });
+        let mut game = Self {
+            width: config.width,
+            height: config.height,
+            snake,
+            snake_direction,
+            food_loc: Location { x: 0, y: 0 },
+            growth_per_food: config.growth_per_food,
+            game_in_progress: true,
+            rng,
+            recording: None,
+        };
 
-        snake.push(Location { x: 1, y: 0 });
+        game.food_loc = match config.first_food {
+            FoodPlacement::Fixed(location) => location,
+            FoodPlacement::Random => game
+                .random_free_location()
+                .expect("A valid config always leaves room for the first food"),
+        };
 
-        snake.push(Location { x: 2, y: 0 });
+        game
+    }
 
-        Self {
-            width,
-            height,
-            tile_size,
-            snake,
-            snake_direction: Direction::Right,
-            food_loc: Location { x: 10, y: 15 },
-            game_in_progress: true,
-        }
+    /// Starts a new game like `new`, but records every step so the game can
+    /// be played back later with `take_replay`.
+    pub fn new_recorded(config: &GameConfig, seed: u64) -> Self {
+        let mut game = Self::new(config, seed);
+        game.recording = Some(Replay::new(*config, seed));
+
+        game
     }
 
-    pub fn render(&mut self, canvas: &mut Canvas<Window>) {
-        let head = self.snake.last().unwrap();
+    /// The recording of this game so far, if it was started with
+    /// `new_recorded`.
+    pub fn take_replay(&mut self) -> Option<Replay> {
+        self.recording.take()
+    }
 
-        canvas.set_draw_color(Self::BACKGROUND_COLOR);
-        canvas.clear();
+    // A random cell the snake isn't on, or `None` once it fills the board.
+    fn random_free_location(&mut self) -> Option<Location> {
+        // The snake covers at most one cell per segment, so there's certainly
+        // room while it's shorter than the board has cells.
+        let cells = usize::from(self.width) * usize::from(self.height);
+        if self.snake.len() >= cells {
+            let free = (0..self.height).any(|y| {
+                (0..self.width).any(|x| {
+                    let location = Location::new(x.try_into().unwrap(), y.try_into().unwrap());
+                    !self.snake.contains(&location)
+                })
+            });
+            if !free {
+                return None;
+            }
+        }
+
+        loop {
+            let location = Location::random_location(self.width, self.height, &mut self.rng);
+            if !self.snake.contains(&location) {
+                return Some(location);
+            }
+        }
+    }
+
+    pub fn render(&self, renderer: &mut impl Renderer, tile_size: u16) {
+        renderer.clear(Self::BACKGROUND_COLOR);
 
         for y in 0..self.height {
             for x in 0..self.width {
@@ -98,31 +470,25 @@ impl Game {
                     y: y.try_into().unwrap(),
                 };
 
-                let snake_here = self.snake.iter().any(|&snake_loc| snake_loc == curr_loc);
+                let snake_here = self.snake.contains(&curr_loc);
 
-                let food_here = (self.food_loc == curr_loc);
+                let food_here = self.food_loc == curr_loc;
 
-                if snake_here {
-                    canvas.set_draw_color(Self::SNAKE_COLOR);
-                    canvas
-                        .fill_rect(Rect::new(
-                            (x * u16::from(self.tile_size)).into(),
-                            (y * u16::from(self.tile_size)).into(),
-                            self.tile_size.into(),
-                            self.tile_size.into(),
-                        ))
-                        .unwrap();
+                let color = if snake_here {
+                    Self::SNAKE_COLOR
                 } else if food_here {
-                    canvas.set_draw_color(Self::FOOD_COLOR);
-                    canvas
-                        .fill_rect(Rect::new(
-                            (x * u16::from(self.tile_size)).into(),
-                            (y * u16::from(self.tile_size)).into(),
-                            self.tile_size.into(),
-                            self.tile_size.into(),
-                        ))
-                        .unwrap();
-                }
+                    Self::FOOD_COLOR
+                } else {
+                    continue;
+                };
+
+                renderer.fill_rect(
+                    i32::from(x) * i32::from(tile_size),
+                    i32::from(y) * i32::from(tile_size),
+                    tile_size.into(),
+                    tile_size.into(),
+                    color,
+                );
             }
         }
     }
@@ -136,6 +502,10 @@ impl Game {
             return GameStatus::Over;
         }
 
+        if let Some(recording) = self.recording.as_mut() {
+            recording.push(self.snake_direction);
+        }
+
         let old_front = self.get_snake_head_location();
         let mut new_front = old_front;
 
@@ -161,7 +531,7 @@ impl Game {
             }
         };
 
-        let collision_with_self = self.snake.iter().any(|&snake_loc| snake_loc == new_front);
+        let collision_with_self = self.snake.contains(&new_front);
 
         if out_of_bounds || collision_with_self {
             self.snake.push(old_front);
@@ -172,21 +542,16 @@ impl Game {
         self.snake.push(new_front);
 
         if new_front == self.food_loc {
-            let mut food_overlap = true;
-            while food_overlap {
-                /*self.food_loc.x += 13;
-                self.food_loc.x %= (self.width as i16);
-                self.food_loc.y += 19;
-                self.food_loc.y %= (self.height as i16);
-                food_overlap = false;*/
-                self.food_loc = Location::random_location(self.width, self.height);
-                food_overlap = self
-                    .snake
-                    .iter()
-                    .any(|&snake_loc| snake_loc == self.food_loc);
+            // With nowhere left for food the game is won, so this is its
+            // last step.
+            match self.random_free_location() {
+                Some(location) => self.food_loc = location,
+                None => self.game_in_progress = false,
             }
 
-            self.snake.insert(0, self.snake[0]);
+            for _ in 0..self.growth_per_food {
+                self.snake.insert(0, self.snake[0]);
+            }
         }
 
         let distance_to_food_x = new_front.x - self.food_loc.x;
@@ -249,3 +614,111 @@ impl Game {
         self.height
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn random_spawn(width: u16, height: u16, initial_length: u16) -> GameConfig {
+        GameConfig {
+            width,
+            height,
+            initial_length,
+            spawn: Spawn::Random,
+            first_food: FoodPlacement::Random,
+            ..GameConfig::default()
+        }
+    }
+
+    #[test]
+    fn random_spawn_must_fit_every_heading() {
+        assert!(random_spawn(10, 3, 4).validate().is_err());
+        assert!(random_spawn(3, 10, 4).validate().is_err());
+
+        // Every heading and position a valid config can pick fits.
+        let config = random_spawn(4, 3, 3);
+        assert!(config.validate().is_ok());
+        for seed in 0..200 {
+            Game::new(&config, seed);
+        }
+    }
+
+    #[test]
+    fn fixed_spawn_must_fit_on_the_board() {
+        let config = GameConfig::sized(3, 3);
+        assert!(config.validate().is_ok());
+        assert!(GameConfig::sized(2, 3).validate().is_err());
+        assert!(GameConfig::sized(1, 1).validate().is_err());
+
+        let off_board = GameConfig {
+            spawn: Spawn::Fixed {
+                head: Location::new(0, 0),
+                direction: Direction::Right,
+            },
+            ..config
+        };
+        assert!(off_board.validate().is_err());
+    }
+
+    #[test]
+    fn rejects_unplayable_configs() {
+        assert!(GameConfig::sized(0, 5).validate().is_err());
+        assert!(GameConfig::sized(40_000, 5).validate().is_err());
+        assert!(random_spawn(5, 5, 0).validate().is_err());
+        assert!(random_spawn(1, 2, 1).validate().is_ok());
+        // No room left for food.
+        assert!(random_spawn(1, 1, 1).validate().is_err());
+
+        let food_off_board = GameConfig {
+            first_food: FoodPlacement::Fixed(Location::new(5, 0)),
+            ..GameConfig::sized(5, 5)
+        };
+        assert!(food_off_board.validate().is_err());
+    }
+
+    #[test]
+    fn filling_the_board_ends_the_game() {
+        let config = GameConfig {
+            width: 2,
+            height: 2,
+            initial_length: 2,
+            spawn: Spawn::Fixed {
+                head: Location::new(1, 0),
+                direction: Direction::Right,
+            },
+            first_food: FoodPlacement::Fixed(Location::new(1, 1)),
+            ..GameConfig::default()
+        };
+        assert!(config.validate().is_ok());
+
+        let mut game = Game::new(&config, 0);
+        let mut last_score = None;
+        for &direction in [Direction::Down, Direction::Left, Direction::Up].iter() {
+            game.turn_snake(direction);
+            match game.step() {
+                GameStatus::InProgress(stats) => last_score = Some(stats.score),
+                GameStatus::Over => break,
+            }
+        }
+
+        // With this seed the second food lands on (0, 1) and the third on
+        // (0, 0), so the last step covers the board. The game stops there
+        // rather than looking forever for somewhere to put more food.
+        assert_eq!(last_score, Some(5));
+        assert!(matches!(game.step(), GameStatus::Over));
+    }
+
+    #[test]
+    fn reading_rejects_unplayable_configs() {
+        let mut bytes = Vec::new();
+        GameConfig::default().write_to(&mut bytes).unwrap();
+        assert!(GameConfig::read_from(&mut &bytes[..]).is_ok());
+
+        // The snake's initial length comes after the width and height.
+        bytes[4..6].copy_from_slice(&0u16.to_le_bytes());
+        assert!(matches!(
+            GameConfig::read_from(&mut &bytes[..]),
+            Err(FormatError::Invalid(_))
+        ));
+    }
+}
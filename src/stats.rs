@@ -0,0 +1,217 @@
+use std::fmt::Display;
+use std::io::{self, Write};
+use std::time::Duration;
+
+/// A summary of one evaluated generation, see `Population::get_stats`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct GenerationStats {
+    /// Numbered from 1, the population as first created.
+    pub generation: usize,
+    pub best_fitness: f64,
+    pub mean_fitness: f64,
+    pub median_fitness: f64,
+    pub worst_fitness: f64,
+    pub fitness_std_dev: f64,
+    /// Best score any agent reached in a single game, for agents that play games.
+    pub best_game_score: Option<u32>,
+    /// Mean over the population of the steps survived per game, for agents
+    /// that play games.
+    pub mean_steps_survived: Option<f64>,
+    /// Standard deviation of each gene across the population, averaged over
+    /// every gene. Falls towards zero as the population converges.
+    pub diversity: f64,
+    /// Wall-clock time spent evaluating the generation.
+    pub evaluation_time: Duration,
+    /// Wall-clock time spent breeding the generation from the last, zero for
+    /// the first.
+    pub breeding_time: Duration,
+}
+
+impl GenerationStats {
+    const FIELDS: [&'static str; 11] = [
+        "generation",
+        "best_fitness",
+        "mean_fitness",
+        "median_fitness",
+        "worst_fitness",
+        "fitness_std_dev",
+        "best_game_score",
+        "mean_steps_survived",
+        "diversity",
+        "evaluation_seconds",
+        "breeding_seconds",
+    ];
+
+    // Every field in the order of `FIELDS`, formatted by `number`, or `None`
+    // where there's nothing to report.
+    fn values(&self, number: fn(&dyn Display) -> String) -> [Option<String>; 11] {
+        [
+            Some(number(&self.generation)),
+            Some(number(&self.best_fitness)),
+            Some(number(&self.mean_fitness)),
+            Some(number(&self.median_fitness)),
+            Some(number(&self.worst_fitness)),
+            Some(number(&self.fitness_std_dev)),
+            self.best_game_score.map(|score| number(&score)),
+            self.mean_steps_survived.map(|steps| number(&steps)),
+            Some(number(&self.diversity)),
+            Some(number(&self.evaluation_time.as_secs_f64())),
+            Some(number(&self.breeding_time.as_secs_f64())),
+        ]
+    }
+}
+
+/// Somewhere to send the stats of each generation.
+pub trait StatsSink {
+    fn record(&mut self, stats: &GenerationStats) -> io::Result<()>;
+}
+
+/// Writes stats as CSV, one row per generation. Missing values are left
+/// empty.
+pub struct CsvSink<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> CsvSink<W> {
+    /// Starts a new file, beginning with a header row.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writeln!(writer, "{}", GenerationStats::FIELDS.join(","))?;
+
+        Ok(Self { writer })
+    }
+
+    /// Carries on a file that already has its header row.
+    pub fn appending(writer: W) -> Self {
+        Self { writer }
+    }
+}
+
+impl<W: Write> StatsSink for CsvSink<W> {
+    fn record(&mut self, stats: &GenerationStats) -> io::Result<()> {
+        let row = stats
+            .values(|val| val.to_string())
+            .iter()
+            .map(|val| val.as_deref().unwrap_or(""))
+            .collect::<Vec<&str>>()
+            .join(",");
+
+        writeln!(self.writer, "{}", row)?;
+        self.writer.flush()
+    }
+}
+
+/// Writes stats as JSON lines, one object per generation. Missing values,
+/// and any that aren't finite, are written as `null`.
+pub struct JsonLinesSink<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> JsonLinesSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+}
+
+impl<W: Write> StatsSink for JsonLinesSink<W> {
+    fn record(&mut self, stats: &GenerationStats) -> io::Result<()> {
+        let fields = GenerationStats::FIELDS
+            .iter()
+            .zip(stats.values(json_number).iter())
+            .map(|(name, val)| format!("\"{}\":{}", name, val.as_deref().unwrap_or("null")))
+            .collect::<Vec<String>>()
+            .join(",");
+
+        writeln!(self.writer, "{{{}}}", fields)?;
+        self.writer.flush()
+    }
+}
+
+// JSON has no NaN or infinity, so they become null.
+fn json_number(val: &dyn Display) -> String {
+    let val = val.to_string();
+    match val.parse::<f64>() {
+        Ok(number) if !number.is_finite() => "null".to_string(),
+        _ => val,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats() -> GenerationStats {
+        GenerationStats {
+            generation: 3,
+            best_fitness: 12.5,
+            mean_fitness: 4.0,
+            median_fitness: 3.25,
+            worst_fitness: -1.0,
+            fitness_std_dev: 2.0,
+            best_game_score: Some(7),
+            mean_steps_survived: Some(40.5),
+            diversity: 0.125,
+            evaluation_time: Duration::from_millis(1500),
+            breeding_time: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn csv_rows_follow_the_header() {
+        let mut bytes = Vec::new();
+        let mut sink = CsvSink::new(&mut bytes).unwrap();
+        sink.record(&stats()).unwrap();
+        sink.record(&GenerationStats {
+            best_game_score: None,
+            mean_steps_survived: None,
+            ..stats()
+        })
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(bytes).unwrap(),
+            "generation,best_fitness,mean_fitness,median_fitness,worst_fitness,fitness_std_dev,\
+             best_game_score,mean_steps_survived,diversity,evaluation_seconds,breeding_seconds\n\
+             3,12.5,4,3.25,-1,2,7,40.5,0.125,1.5,0\n\
+             3,12.5,4,3.25,-1,2,,,0.125,1.5,0\n"
+        );
+    }
+
+    #[test]
+    fn appending_csv_skips_the_header() {
+        let mut bytes = Vec::new();
+        CsvSink::appending(&mut bytes).record(&stats()).unwrap();
+
+        assert_eq!(
+            String::from_utf8(bytes).unwrap(),
+            "3,12.5,4,3.25,-1,2,7,40.5,0.125,1.5,0\n"
+        );
+    }
+
+    #[test]
+    fn json_lines_write_missing_and_non_finite_values_as_null() {
+        let mut bytes = Vec::new();
+        let mut sink = JsonLinesSink::new(&mut bytes);
+        sink.record(&stats()).unwrap();
+        sink.record(&GenerationStats {
+            best_fitness: f64::INFINITY,
+            worst_fitness: f64::NEG_INFINITY,
+            fitness_std_dev: f64::NAN,
+            best_game_score: None,
+            mean_steps_survived: None,
+            ..stats()
+        })
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(bytes).unwrap(),
+            "{\"generation\":3,\"best_fitness\":12.5,\"mean_fitness\":4,\"median_fitness\":3.25,\
+             \"worst_fitness\":-1,\"fitness_std_dev\":2,\"best_game_score\":7,\
+             \"mean_steps_survived\":40.5,\"diversity\":0.125,\"evaluation_seconds\":1.5,\
+             \"breeding_seconds\":0}\n\
+             {\"generation\":3,\"best_fitness\":null,\"mean_fitness\":4,\"median_fitness\":3.25,\
+             \"worst_fitness\":null,\"fitness_std_dev\":null,\"best_game_score\":null,\
+             \"mean_steps_survived\":null,\"diversity\":0.125,\"evaluation_seconds\":1.5,\
+             \"breeding_seconds\":0}\n"
+        );
+    }
+}
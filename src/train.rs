@@ -0,0 +1,267 @@
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use rand::Rng;
+
+use crate::activation::Activation;
+use crate::agent;
+use crate::format::FormatError;
+use crate::population::{BreedingConfig, Population};
+use crate::rng;
+use crate::snake::GameConfig;
+use crate::stats::{CsvSink, GenerationStats, JsonLinesSink, StatsSink};
+
+const CHECKPOINT_FILE: &str = "checkpoint.pop";
+
+/// Settings for a headless training run.
+#[derive(Clone, Debug)]
+pub struct TrainOptions {
+    pub population_size: usize,
+    /// Number of generations to train for, or `None` to train until stopped.
+    pub generations: Option<usize>,
+    pub seed: u64,
+    pub game_config: GameConfig,
+    pub output_dir: PathBuf,
+    pub checkpoint_every: usize,
+    pub breeding: BreedingConfig,
+    /// Activation of the inner layers of each snake's network.
+    pub hidden_activation: Activation,
+    /// Activation of the output layer of each snake's network.
+    pub output_activation: Activation,
+    /// Carry on from the checkpoint in `output_dir` if there is one. The
+    /// checkpoint's population, seed, breeding config and activations are
+    /// used instead of the ones here, and `generations` counts from the start of the original
+    /// run.
+    pub resume: bool,
+}
+
+impl Default for TrainOptions {
+    fn default() -> Self {
+        Self {
+            population_size: 5_000,
+            generations: None,
+            seed: 0,
+            game_config: GameConfig::default(),
+            output_dir: PathBuf::from("training"),
+            checkpoint_every: 10,
+            breeding: BreedingConfig::default(),
+            hidden_activation: Activation::Sigmoid,
+            output_activation: Activation::Sigmoid,
+            resume: false,
+        }
+    }
+}
+
+/// Evolves a population of snakes without opening a window. Per-generation
+/// best scores go to stdout, and full stats to `stats.csv` and `stats.jsonl`
+/// in the output directory. Every
+/// `checkpoint_every` generations, and when training stops, the population
+/// is checkpointed to `checkpoint.pop`, the best network ever seen is saved as
+/// `best.net`, and its highest scoring evaluation game as `best.rpl`.
+pub fn run(options: &TrainOptions) -> Result<(), FormatError> {
+    run_with(options, |_, _| true)
+}
+
+/// Same as `run`, but calls `on_generation` with the stats of each
+/// population once it's been evaluated, along with the population itself,
+/// e.g. to show off its best agent. Training stops early, after writing a
+/// final checkpoint, if it returns false.
+pub fn run_with<F>(options: &TrainOptions, mut on_generation: F) -> Result<(), FormatError>
+where
+    F: FnMut(&GenerationStats, &Population<agent::Snake>) -> bool,
+{
+    fs::create_dir_all(&options.output_dir)?;
+    let checkpoint_path = options.output_dir.join(CHECKPOINT_FILE);
+    let csv_path = options.output_dir.join("stats.csv");
+    let json_path = options.output_dir.join("stats.jsonl");
+
+    let (mut population, mut csv_sink) = if options.resume && checkpoint_path.exists() {
+        let population = Population::load_checkpoint(&checkpoint_path)?;
+        println!(
+            "Resuming from generation {}",
+            population.get_generation() + 1
+        );
+
+        // Anything recorded after the checkpoint is about to be recorded
+        // again, so keep just the header and the generations it has seen.
+        let recorded = population.get_generation();
+        truncate_lines(&json_path, recorded)?;
+        if csv_path.exists() {
+            truncate_lines(&csv_path, recorded + 1)?;
+            let csv_file = OpenOptions::new().append(true).open(&csv_path)?;
+
+            (population, CsvSink::appending(csv_file))
+        } else {
+            (population, CsvSink::new(File::create(&csv_path)?)?)
+        }
+    } else {
+        let mut rng = rng::from_seed(options.seed);
+        let population = Population::with_config(
+            (0..options.population_size)
+                .map(|_| {
+                    agent::Snake::with_activations(
+                        options.game_config,
+                        options.hidden_activation,
+                        options.output_activation,
+                        &mut rng,
+                    )
+                })
+                .collect(),
+            rng.gen(),
+            options.breeding,
+        );
+
+        if json_path.exists() {
+            fs::remove_file(&json_path)?;
+        }
+
+        (population, CsvSink::new(File::create(&csv_path)?)?)
+    };
+    let mut json_sink = JsonLinesSink::new(
+        OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&json_path)?,
+    );
+
+    loop {
+        // Generations are numbered from 1, the population as first created.
+        let generation = population.get_generation() + 1;
+        if options.generations.is_some_and(|limit| generation > limit) {
+            break;
+        }
+
+        population.evaluate();
+        let (best, best_score) = population.get_best().unwrap();
+        println!("Best score of generation {}: {}", generation, best_score);
+
+        let stats = population.get_stats().unwrap();
+        csv_sink.record(&stats)?;
+        json_sink.record(&stats)?;
+
+        let keep_going = on_generation(&stats, &population);
+        let checkpoint = generation % options.checkpoint_every == 0
+            || options.generations == Some(generation)
+            || !keep_going;
+        if checkpoint {
+            // Without elites a generation can do worse than the last, so save
+            // whichever is the best seen so far.
+            let best = match population.get_hall_of_fame().first() {
+                Some((champion, champion_score)) if *champion_score > best_score => champion,
+                _ => best,
+            };
+            save_best(options, &population, best)?;
+        }
+
+        // Checkpoint the bred population, so resuming carries on with the next generation.
+        population = population.breed();
+        if checkpoint {
+            population.save_checkpoint(&checkpoint_path)?;
+        }
+
+        if !keep_going {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+// Cuts the file at `path` down to its first `count` lines, if it exists.
+fn truncate_lines(path: &Path, count: usize) -> io::Result<()> {
+    if !path.exists() {
+        return Ok(());
+    }
+
+    let contents = fs::read_to_string(path)?;
+    let len = contents
+        .split_inclusive('\n')
+        .take(count)
+        .map(str::len)
+        .sum::<usize>();
+    OpenOptions::new()
+        .write(true)
+        .open(path)?
+        .set_len(len as u64)
+}
+
+fn save_best(
+    options: &TrainOptions,
+    population: &Population<agent::Snake>,
+    best: &agent::Snake,
+) -> Result<(), FormatError> {
+    best.get_network()
+        .save(options.output_dir.join("best.net"))?;
+
+    let best_game = best
+        .record_evaluation(population.get_evaluation_seed())
+        .into_iter()
+        .max_by(|(_, score1), (_, score2)| score1.partial_cmp(score2).unwrap());
+    if let Some((replay, _)) = best_game {
+        replay.save(options.output_dir.join("best.rpl"))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The first field of every line, which is the generation for both
+    // sinks.
+    fn first_fields(contents: &str) -> Vec<&str> {
+        contents
+            .lines()
+            .map(|line| line.split(',').next().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn resuming_drops_stats_recorded_after_the_checkpoint() {
+        let output_dir = std::env::temp_dir().join(format!("neural-resume-{}", std::process::id()));
+        let options = TrainOptions {
+            population_size: 4,
+            generations: Some(2),
+            game_config: GameConfig::sized(6, 6),
+            output_dir: output_dir.clone(),
+            checkpoint_every: 2,
+            ..TrainOptions::default()
+        };
+        run(&options).unwrap();
+
+        // Carry on past the checkpoint, then go back to it as if the run had
+        // been killed before checkpointing again.
+        let checkpoint_path = output_dir.join(CHECKPOINT_FILE);
+        let checkpoint = fs::read(&checkpoint_path).unwrap();
+        run(&TrainOptions {
+            generations: Some(3),
+            resume: true,
+            ..options.clone()
+        })
+        .unwrap();
+        fs::write(&checkpoint_path, checkpoint).unwrap();
+        run(&TrainOptions {
+            generations: Some(4),
+            resume: true,
+            ..options
+        })
+        .unwrap();
+
+        let csv = fs::read_to_string(output_dir.join("stats.csv")).unwrap();
+        let json_lines = fs::read_to_string(output_dir.join("stats.jsonl")).unwrap();
+        fs::remove_dir_all(&output_dir).unwrap();
+
+        assert_eq!(first_fields(&csv), ["generation", "1", "2", "3", "4"]);
+        assert_eq!(
+            first_fields(&json_lines),
+            [
+                "{\"generation\":1",
+                "{\"generation\":2",
+                "{\"generation\":3",
+                "{\"generation\":4"
+            ]
+        );
+    }
+}